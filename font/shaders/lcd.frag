@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec4 out_color;
+in vec2 out_uv;
+
+//Dual source blending, the second output is the per channel alpha.
+//https://www.khronos.org/opengl/wiki/Blending#Dual_Source_Blending
+layout(location = 0, index = 0) out vec4 color;
+layout(location = 0, index = 1) out vec4 mask;
+
+uniform sampler2D image;
+
+void main() {
+    vec3 coverage = texture(image, out_uv).rgb * out_color.a;
+    color = vec4(out_color.rgb * coverage, 1.0);
+    mask = vec4(coverage, 1.0);
+}
@@ -0,0 +1,52 @@
+#version 330 core
+
+in vec4 out_color;
+in vec2 out_uv;
+
+out vec4 color;
+
+uniform sampler2D image;
+
+//Distances are in texture values, 0.5 is the edge of the glyph.
+uniform vec4 outline_color;
+uniform float outline_width;
+
+uniform vec4 shadow_color;
+//In texels with y going up, the atlas rows go down.
+uniform vec2 shadow_offset;
+uniform float shadow_softness;
+
+uniform vec4 glow_color;
+uniform float glow_width;
+
+vec4 over(vec4 top, vec4 bottom) {
+    float a = top.a + bottom.a * (1.0 - top.a);
+    if (a <= 0.0) {
+        return vec4(0.0);
+    }
+    vec3 rgb = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / a;
+    return vec4(rgb, a);
+}
+
+void main() {
+    float distance = texture(image, out_uv).x;
+    //Antialias over roughly one screen pixel, no matter how much the glyph is scaled.
+    float aa = max(fwidth(distance), 0.0001);
+
+    float fill = smoothstep(0.5 - aa, 0.5 + aa, distance);
+    float border = smoothstep(0.5 - outline_width - aa, 0.5 - outline_width + aa, distance);
+
+    vec4 text = outline_width > 0.0 ? mix(outline_color, out_color, fill) : out_color;
+    text.a *= border;
+
+    float glow = glow_width > 0.0 ? smoothstep(0.5 - glow_width, 0.5, distance) : 0.0;
+    vec4 halo = vec4(glow_color.rgb, glow_color.a * glow);
+
+    vec2 texel = 1.0 / vec2(textureSize(image, 0));
+    float shadow_distance = texture(image, out_uv - vec2(shadow_offset.x, -shadow_offset.y) * texel).x;
+    float softness = max(shadow_softness, aa);
+    float shadow = smoothstep(0.5 - softness, 0.5 + softness, shadow_distance);
+    vec4 drop = vec4(shadow_color.rgb, shadow_color.a * shadow);
+
+    color = over(text, over(halo, drop));
+}
@@ -6,7 +6,13 @@ in vec2 out_uv;
 out vec4 color;
 
 uniform sampler2D image;
+//Emoji and other color glyphs, their u is shifted by `COLOR_UV_OFFSET`.
+uniform sampler2D color_image;
 
 void main() {
-    color = texture(image, out_uv).x * out_color;
-}
\ No newline at end of file
+    if (out_uv.x >= 2.0) {
+        color = texture(color_image, out_uv - vec2(2.0, 0.0)) * vec4(1.0, 1.0, 1.0, out_color.a);
+    } else {
+        color = texture(image, out_uv).x * out_color;
+    }
+}
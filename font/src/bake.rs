@@ -0,0 +1,343 @@
+use crate::*;
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder, ImageFormat};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"ATLS";
+const VERSION: u32 = 1;
+
+/// Baked atlases are split in two, the texture as a PNG and a little endian metrics file:
+///
+/// ```text
+/// magic "ATLS", version u32
+/// mode u8, pixel order u8, lcd filter u8
+/// width i32, height i32, size u32
+/// line height, ascender, descender, line gap f32, units per em u16
+/// fallback face u32, fallback id u32
+/// face count u32
+/// variation count u32, then for each variation: tag 4 x u8, value f32
+/// subpixel positions u8, embolden f32
+/// glyph count u32, then for each glyph:
+///     face u32, id u32, subpixel u8,
+///     advance 2 x f32, width f32, height f32, bearing 2 x f32, x i32, y i32
+/// ```
+///
+/// Glyphs that aren't in the file can't be drawn, so [`Atlas::preload`] everything first.
+/// The color page isn't saved, baked atlases draw emoji as the fallback glyph.
+impl Atlas {
+    /// The texture as a grayscale PNG, or RGB for [`Mode::Lcd`].
+    pub fn bake_png(&self) -> Result<Vec<u8>> {
+        let color = match self.mode.channels() {
+            1 => ColorType::L8,
+            3 => ColorType::Rgb8,
+            _ => ColorType::Rgba8,
+        };
+
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(
+                &self.bitmap.pixels,
+                self.bitmap.width as u32,
+                self.bitmap.height as u32,
+                color,
+            )
+            .map_err(Error::other)?;
+        Ok(png)
+    }
+
+    pub fn bake_metrics(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend(MAGIC);
+        out.extend(VERSION.to_le_bytes());
+
+        let mode = match self.mode {
+            Mode::Coverage => [0, 0, 0],
+            Mode::Sdf => [1, 0, 0],
+            Mode::Lcd { order, filter } => {
+                let order = match order {
+                    PixelOrder::Rgb => 0,
+                    PixelOrder::Bgr => 1,
+                };
+                let filter = match filter {
+                    LcdFilter::None => 0,
+                    LcdFilter::Default => 1,
+                    LcdFilter::Light => 2,
+                    LcdFilter::Legacy => 3,
+                };
+                [2, order, filter]
+            }
+            Mode::Color => [3, 0, 0],
+        };
+        out.extend(mode);
+
+        out.extend(self.bitmap.width.to_le_bytes());
+        out.extend(self.bitmap.height.to_le_bytes());
+        out.extend(self.size.to_le_bytes());
+        for metric in [
+            self.line_height,
+            self.ascender,
+            self.descender,
+            self.line_gap,
+        ] {
+            out.extend(metric.to_le_bytes());
+        }
+        out.extend(self.units_per_em.to_le_bytes());
+        out.extend(face_index(self.fallback.face).to_le_bytes());
+        out.extend(self.fallback.id.to_le_bytes());
+        out.extend((self.faces.len() as u32).to_le_bytes());
+        out.extend((self.variations.len() as u32).to_le_bytes());
+        for variation in &self.variations {
+            out.extend(variation.tag);
+            out.extend(variation.value.to_le_bytes());
+        }
+        out.push(self.subpixel);
+        //Emboldening widens the advances, so layout needs it even though the glyphs are rasterized.
+        out.extend(self.options.embolden.to_le_bytes());
+
+        //Sorted so baking the same atlas twice gives the same file.
+        let mut keys: Vec<&GlyphKey> = self.bitmap.glyphs.keys().collect();
+        keys.sort_by_key(|key| (key.face, key.id, key.subpixel));
+
+        out.extend((keys.len() as u32).to_le_bytes());
+        for key in keys {
+            let glyph = &self.bitmap.glyphs[key];
+            out.extend(face_index(key.face).to_le_bytes());
+            out.extend(key.id.to_le_bytes());
+            out.push(key.subpixel);
+            for value in [
+                glyph.advance.x,
+                glyph.advance.y,
+                glyph.width,
+                glyph.height,
+                glyph.bearing.x,
+                glyph.bearing.y,
+            ] {
+                out.extend(value.to_le_bytes());
+            }
+            out.extend(glyph.x.to_le_bytes());
+            out.extend(glyph.y.to_le_bytes());
+        }
+
+        out
+    }
+
+    /// Write the texture and metrics so [`load_baked`] can rebuild the atlas without FreeType.
+    pub fn save(&self, png: impl AsRef<Path>, metrics: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(png, self.bake_png()?)?;
+        std::fs::write(metrics, self.bake_metrics())
+    }
+}
+
+/// [`SOLID`] uses `usize::MAX` which doesn't fit in the file.
+fn face_index(face: usize) -> u32 {
+    face.min(u32::MAX as usize) as u32
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(invalid("atlas metrics are truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    fn face(&mut self) -> Result<usize> {
+        Ok(match self.u32()? {
+            u32::MAX => usize::MAX,
+            face => face as usize,
+        })
+    }
+}
+
+/// Rebuild an atlas saved with [`Atlas::save`] and upload it.
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+pub unsafe fn load_baked(
+    rd: &Renderer,
+    png: &[u8],
+    metrics: &[u8],
+    fonts: &[&'static [u8]],
+) -> Result<Atlas> {
+    let mut atlas = Atlas::from_baked(png, metrics, fonts)?;
+    atlas.upload(rd.gl);
+    Ok(atlas)
+}
+
+impl Atlas {
+    /// Rebuild an atlas saved with [`Atlas::save`] without touching the GPU. `fonts` are the same
+    /// faces the atlas was baked from, primary first, they're only used for shaping.
+    pub fn from_baked(png: &[u8], metrics: &[u8], fonts: &[&'static [u8]]) -> Result<Atlas> {
+        let mut reader = Reader { bytes: metrics };
+
+        if reader.take::<4>()? != MAGIC {
+            return Err(invalid("not an atlas metrics file"));
+        }
+        if reader.u32()? != VERSION {
+            return Err(invalid("unsupported atlas metrics version"));
+        }
+
+        let mode = match [reader.u8()?, reader.u8()?, reader.u8()?] {
+            [0, _, _] => Mode::Coverage,
+            [1, _, _] => Mode::Sdf,
+            [2, order, filter] => Mode::Lcd {
+                order: match order {
+                    0 => PixelOrder::Rgb,
+                    _ => PixelOrder::Bgr,
+                },
+                filter: match filter {
+                    0 => LcdFilter::None,
+                    1 => LcdFilter::Default,
+                    2 => LcdFilter::Light,
+                    _ => LcdFilter::Legacy,
+                },
+            },
+            [3, _, _] => Mode::Color,
+            _ => return Err(invalid("unknown atlas mode")),
+        };
+
+        let width = reader.i32()?;
+        let height = reader.i32()?;
+        //Checked before the bitmap is allocated.
+        if !(1..=ATLAS_WIDTH).contains(&width) || !(1..=MAX_ATLAS_HEIGHT).contains(&height) {
+            return Err(invalid("atlas size is out of range"));
+        }
+        let size = reader.u32()?;
+        let line_height = reader.f32()?;
+        let ascender = reader.f32()?;
+        let descender = reader.f32()?;
+        let line_gap = reader.f32()?;
+        let units_per_em = reader.u16()?;
+        let fallback = GlyphKey {
+            face: reader.face()?,
+            id: reader.u32()?,
+            subpixel: 0,
+        };
+
+        if reader.u32()? as usize != fonts.len() || fonts.is_empty() {
+            return Err(invalid("atlas was baked with a different number of faces"));
+        }
+
+        let mut variations = Vec::new();
+        for _ in 0..reader.u32()? {
+            let tag = reader.take::<4>()?;
+            variations.push(Variation::new(&tag, reader.f32()?));
+        }
+        let subpixel = reader.u8()?.max(1);
+        let options = FontOptions {
+            embolden: reader.f32()?,
+            ..Default::default()
+        };
+
+        let count = reader.u32()?;
+        let mut bitmap = AtlasBitmap::with_size(width, height, mode);
+        for _ in 0..count {
+            let key = GlyphKey {
+                face: reader.face()?,
+                id: reader.u32()?,
+                subpixel: reader.u8()?,
+            };
+            let mut glyph = Glyph {
+                advance: Vec2::new(reader.f32()?, reader.f32()?),
+                width: reader.f32()?,
+                height: reader.f32()?,
+                bearing: Vec2::new(reader.f32()?, reader.f32()?),
+                x: reader.i32()?,
+                y: reader.i32()?,
+                ..Default::default()
+            };
+            let inside = |start: i32, size: f32, limit: i32| {
+                start >= 0 && size >= 0.0 && start as f32 + size <= limit as f32
+            };
+            if !inside(glyph.x, glyph.width, width) || !inside(glyph.y, glyph.height, height) {
+                return Err(invalid("glyph is outside the atlas"));
+            }
+            glyph.uv = UvRect::new(
+                glyph.x,
+                glyph.y,
+                glyph.width as i32,
+                glyph.height as i32,
+                width,
+                height,
+            );
+            bitmap.glyphs.insert(key, glyph);
+        }
+
+        let image = image::load_from_memory_with_format(png, ImageFormat::Png)
+            .map_err(|_| invalid("atlas texture isn't a valid PNG"))?;
+        if image.width() != width as u32 || image.height() != height as u32 {
+            return Err(invalid("atlas texture doesn't match the metrics"));
+        }
+        bitmap.pixels = match mode.channels() {
+            1 => image.into_luma8().into_raw(),
+            3 => image.into_rgb8().into_raw(),
+            _ => image.into_rgba8().into_raw(),
+        };
+
+        let faces = fonts
+            .iter()
+            .map(|font| {
+                let mut shaper = Shaper::new(font, size as f32);
+                shaper.set_variations(&variations);
+                FontFace {
+                    #[cfg(feature = "freetype")]
+                    face: None,
+                    #[cfg(feature = "freetype")]
+                    bitmap_scale: 1.0,
+                    shaper,
+                }
+            })
+            .collect();
+
+        Ok(Atlas {
+            size,
+            mode,
+            line_height,
+            ascender,
+            descender,
+            line_gap,
+            units_per_em,
+            bitmap,
+            texture: None,
+            color: None,
+            color_texture: None,
+            faces,
+            fallback,
+            variations,
+            synthetic: Synthetic::default(),
+            subpixel,
+            options,
+        })
+    }
+}
@@ -0,0 +1,120 @@
+use crate::*;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Initial size of the atlas texture, it will double in height when it runs out of space.
+pub const ATLAS_WIDTH: i32 = 1024;
+pub const ATLAS_HEIGHT: i32 = 256;
+/// Tallest an atlas grows to, [`Atlas::upload`] lowers it to what the GPU supports.
+pub const MAX_ATLAS_HEIGHT: i32 = 8192;
+
+/// Glyph pixels and where they were packed. Lives on the CPU so atlases can be built
+/// and tested without a GL context, [`Atlas::upload`] copies it into a texture.
+#[derive(Debug, Clone)]
+pub struct AtlasBitmap {
+    pub width: i32,
+    pub height: i32,
+    pub max_height: i32,
+    pub mode: Mode,
+    /// Rows are stored top to bottom with [`Mode::channels`] bytes per pixel.
+    pub pixels: Vec<u8>,
+    pub packer: Packer,
+    /// Keyed by glyph id, so ligatures and alternates get their own entry.
+    pub glyphs: HashMap<GlyphKey, Glyph>,
+    /// Rows written since the last upload.
+    pub dirty: Option<Range<i32>>,
+    /// The size changed since the last upload, so the whole texture has to be recreated.
+    pub resized: bool,
+}
+
+impl AtlasBitmap {
+    pub fn new(mode: Mode) -> Self {
+        Self::with_size(ATLAS_WIDTH, ATLAS_HEIGHT, mode)
+    }
+
+    pub fn with_size(width: i32, height: i32, mode: Mode) -> Self {
+        Self {
+            width,
+            height,
+            max_height: MAX_ATLAS_HEIGHT,
+            mode,
+            //If we don't zero this texture, bad things will happen.
+            pixels: vec![0; (width * height * mode.channels()) as usize],
+            packer: Packer::new(width, height),
+            glyphs: HashMap::new(),
+            dirty: None,
+            resized: true,
+        }
+    }
+
+    /// Copy `buffer` into free space and remember where it went under `key`.
+    /// `None` once the bitmap can't grow any further.
+    pub fn insert(&mut self, key: GlyphKey, mut glyph: Glyph, buffer: &[u8]) -> Option<Glyph> {
+        let (w, h) = (glyph.width as i32, glyph.height as i32);
+
+        let (x, y) = loop {
+            match self.packer.pack(w, h) {
+                Some(position) => break position,
+                None => self.grow()?,
+            }
+        };
+
+        glyph.x = x;
+        glyph.y = y;
+        glyph.uv = UvRect::new(x, y, w, h, self.width, self.height);
+
+        let channels = self.mode.channels();
+        let stride = (w * channels) as usize;
+        for row in 0..h {
+            let src = row as usize * stride;
+            let dst = (((y + row) * self.width + x) * channels) as usize;
+            self.pixels[dst..dst + stride].copy_from_slice(&buffer[src..src + stride]);
+        }
+
+        self.dirty = match self.dirty.take() {
+            Some(rows) => Some(rows.start.min(y)..rows.end.max(y + h)),
+            None => Some(y..y + h),
+        };
+
+        self.glyphs.insert(key, glyph);
+        Some(glyph)
+    }
+
+    /// Forget every glyph but keep the size, the whole texture is sent again on the next upload.
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.packer = Packer::new(self.width, self.height);
+        self.glyphs.clear();
+        self.dirty = None;
+        self.resized = true;
+    }
+
+    /// Double the height and move the existing glyphs over.
+    /// `None` once the bitmap would be taller than `max_height`.
+    fn grow(&mut self) -> Option<()> {
+        let height = self.height * 2;
+        if height > self.max_height {
+            return None;
+        }
+
+        //Rows are stored top to bottom so growing only appends zeroes.
+        self.pixels
+            .resize((self.width * height * self.mode.channels()) as usize, 0);
+        self.height = height;
+        self.packer.grow(height);
+        self.resized = true;
+
+        for glyph in self.glyphs.values_mut() {
+            glyph.uv = UvRect::new(
+                glyph.x,
+                glyph.y,
+                glyph.width as i32,
+                glyph.height as i32,
+                self.width,
+                self.height,
+            );
+        }
+
+        Some(())
+    }
+}
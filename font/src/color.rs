@@ -0,0 +1,90 @@
+#[cfg(feature = "freetype")]
+use crate::*;
+#[cfg(feature = "freetype")]
+use freetype::{Face, GlyphSlot};
+
+/// Added to the horizontal UVs of color glyphs. `text.frag` samples anything past it from the
+/// color page untinted, so color and normal glyphs can share a draw call.
+pub const COLOR_UV_OFFSET: f32 = 2.0;
+
+/// FreeType's color bitmaps are premultiplied BGRA, the color page is straight RGBA.
+pub fn bgra_to_rgba(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            if let Some(value) = (*channel as u32 * 255 + alpha / 2).checked_div(alpha) {
+                *channel = value.min(255) as u8;
+            }
+        }
+    }
+}
+
+/// Bitmap emoji fonts only come in a few sizes, pick the closest strike and return the scale
+/// from it to `size`. Rounding up keeps the glyphs sharp since they're only ever scaled down.
+#[cfg(feature = "freetype")]
+pub(crate) fn select_strike(face: &mut Face<&'static [u8]>, size: u32) -> f32 {
+    let raw = face.raw();
+    let strikes: Vec<f32> = (0..raw.num_fixed_sizes as usize)
+        .map(|i| unsafe { (*raw.available_sizes.add(i)).y_ppem as f32 / 64.0 })
+        .collect();
+
+    let size = size as f32;
+    let by_size = |a: &&f32, b: &&f32| a.total_cmp(b);
+    let Some(strike) = strikes
+        .iter()
+        .filter(|&&ppem| ppem >= size)
+        .min_by(by_size)
+        .or_else(|| strikes.iter().max_by(by_size))
+    else {
+        return 1.0;
+    };
+
+    let index = strikes.iter().position(|ppem| ppem == strike).unwrap();
+    unsafe {
+        let error = freetype::ffi::FT_Select_Size(face.raw_mut(), index as i32);
+        assert_eq!(error, 0, "FT_Select_Size failed");
+    }
+    size / strike
+}
+
+/// A CBDT, sbix or COLR glyph that FreeType rendered in color, scaled down from its strike.
+#[cfg(feature = "freetype")]
+pub(crate) fn color_glyph(slot: &GlyphSlot, scale: f32) -> (Glyph, Vec<u8>) {
+    let bitmap = slot.bitmap();
+    let (width, height) = (bitmap.width() as u32, bitmap.rows() as u32);
+    let stride = width as usize * 4;
+
+    let mut pixels = Vec::with_capacity(stride * height as usize);
+    for row in bitmap_rows(bitmap.buffer(), bitmap.pitch()) {
+        pixels.extend_from_slice(&row[..stride]);
+    }
+
+    //Filter while the colors are still premultiplied so transparent pixels don't darken the edges.
+    let (width, height, mut pixels) = if scale != 1.0 && width > 0 && height > 0 {
+        let image = image::RgbaImage::from_raw(width, height, pixels).unwrap();
+        let w = ((width as f32 * scale).round() as u32).max(1);
+        let h = ((height as f32 * scale).round() as u32).max(1);
+        let scaled = image::imageops::resize(&image, w, h, image::imageops::FilterType::Triangle);
+        (w, h, scaled.into_raw())
+    } else {
+        (width, height, pixels)
+    };
+    bgra_to_rgba(&mut pixels);
+
+    let glyph = Glyph {
+        advance: Vec2::new(
+            slot.advance().x as f32 / 64.0 * scale,
+            slot.advance().y as f32 / 64.0 * scale,
+        ),
+        width: width as f32,
+        height: height as f32,
+        bearing: Vec2::new(
+            (slot.bitmap_left() as f32 * scale).round(),
+            (slot.bitmap_top() as f32 * scale).round(),
+        ),
+        color: true,
+        ..Default::default()
+    };
+    (glyph, pixels)
+}
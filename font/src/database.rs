@@ -0,0 +1,237 @@
+#[cfg(feature = "freetype")]
+use crate::*;
+use rustybuzz::ttf_parser::name::Name;
+use rustybuzz::ttf_parser::{name_id, Face};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Fonts that ship with the crate, used when nothing on the system matches.
+pub const BUNDLED_FONTS: [&[u8]; 3] = [
+    include_bytes!("../CascadiaMono.ttf"),
+    include_bytes!("../CascadiaCode.ttf"),
+    include_bytes!("../JetBrainsMono.ttf"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontSource {
+    Path(PathBuf),
+    Bundled(&'static [u8]),
+}
+
+/// Names read from a font's `name` and `OS/2` tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontInfo {
+    pub source: FontSource,
+    /// e.g. "JetBrains Mono".
+    pub family: String,
+    /// e.g. "Bold Italic".
+    pub style: String,
+    /// 100 to 900, 400 is regular and 700 is bold.
+    pub weight: u16,
+    pub italic: bool,
+}
+
+impl FontInfo {
+    /// `None` if `data` isn't a font or has no family name.
+    /// Only the first face of a collection is used, that's the one [`Atlas`](crate::Atlas) loads.
+    pub fn parse(source: FontSource, data: &[u8]) -> Option<Self> {
+        let face = Face::parse(data, 0).ok()?;
+
+        //The typographic names group every weight under one family, the legacy ones
+        //split them into families of four like "JetBrains Mono ExtraBold".
+        let family = name(&face, name_id::TYPOGRAPHIC_FAMILY).or(name(&face, name_id::FAMILY))?;
+        let style = name(&face, name_id::TYPOGRAPHIC_SUBFAMILY)
+            .or(name(&face, name_id::SUBFAMILY))
+            .unwrap_or_else(|| "Regular".to_string());
+
+        Some(Self {
+            source,
+            family,
+            style,
+            weight: face.weight().to_number(),
+            italic: face.is_italic(),
+        })
+    }
+}
+
+/// English if there is one, otherwise the first name that decodes.
+fn name(face: &Face, id: u16) -> Option<String> {
+    let names = face.names();
+    let mut candidates = names.into_iter().filter(|name| name.name_id == id);
+    let english = candidates
+        .clone()
+        .find(|name| name.language_id == 0x0409)
+        .and_then(decode);
+    english.or_else(|| candidates.find_map(decode))
+}
+
+/// Unicode names are UTF-16BE, ttf-parser only decodes them with its `std` feature.
+fn decode(name: Name) -> Option<String> {
+    if !name.is_unicode() {
+        return None;
+    }
+    let units: Vec<u16> = name
+        .name
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+/// Lower case without spaces, dashes or underscores, so "JetBrains Mono" matches "jetbrainsmono".
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Weight and italic from the style part of a request, "Bold Italic" is `(700, true)`.
+fn parse_style(style: &str) -> (u16, bool) {
+    let style = normalize(style);
+    let italic = style.contains("italic") || style.contains("oblique");
+
+    //Longest names first so "extrabold" isn't read as "bold".
+    let weight = [
+        ("extralight", 200),
+        ("ultralight", 200),
+        ("extrabold", 800),
+        ("ultrabold", 800),
+        ("semibold", 600),
+        ("demibold", 600),
+        ("medium", 500),
+        ("light", 300),
+        ("black", 900),
+        ("heavy", 900),
+        ("thin", 100),
+        ("bold", 700),
+    ]
+    .iter()
+    .find(|(word, _)| style.contains(word))
+    .map_or(400, |(_, weight)| *weight);
+
+    (weight, italic)
+}
+
+/// Fonts installed on the system, looked up by name.
+///
+/// ```rs
+/// let mut db = FontDatabase::system();
+/// let mut atlas = db.load_font(&rd, "JetBrains Mono Bold", 32);
+/// ```
+#[derive(Debug, Default)]
+pub struct FontDatabase {
+    pub fonts: Vec<FontInfo>,
+    /// Files read so far. Atlases keep a reference to their font for as long as they live,
+    /// so each file is leaked once and shared after that.
+    loaded: HashMap<PathBuf, &'static [u8]>,
+}
+
+impl FontDatabase {
+    /// Only the bundled fonts.
+    pub fn new() -> Self {
+        let mut db = Self::default();
+        for data in BUNDLED_FONTS {
+            if let Some(info) = FontInfo::parse(FontSource::Bundled(data), data) {
+                db.fonts.push(info);
+            }
+        }
+        db
+    }
+
+    /// The bundled fonts plus everything in the standard font directories.
+    pub fn system() -> Self {
+        let mut db = Self::new();
+        db.scan("/usr/share/fonts");
+        db.scan("/usr/local/share/fonts");
+        if let Some(home) = std::env::var_os("HOME") {
+            let home = PathBuf::from(home);
+            db.scan(home.join(".local/share/fonts"));
+            db.scan(home.join(".fonts"));
+        }
+        db
+    }
+
+    /// Add every font under `dir`, missing directories and unreadable files are skipped.
+    pub fn scan(&mut self, dir: impl AsRef<Path>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            //Doesn't follow symlinks, a link back up the tree would never end.
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                self.scan(&path);
+                continue;
+            }
+
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase);
+            if !matches!(extension.as_deref(), Some("ttf" | "otf" | "ttc" | "otc")) {
+                continue;
+            }
+
+            if let Ok(data) = std::fs::read(&path) {
+                if let Some(info) = FontInfo::parse(FontSource::Path(path), &data) {
+                    self.fonts.push(info);
+                }
+            }
+        }
+    }
+
+    /// Find the closest match for a name like "JetBrains Mono Bold Italic". The family has to
+    /// match, then the italic flag, then the weight closest to what was asked for.
+    pub fn query(&self, name: &str) -> Option<&FontInfo> {
+        let name = normalize(name);
+
+        //The longest family that prefixes the request, so "Cascadia Code" doesn't become "Cascadia".
+        let family = self
+            .fonts
+            .iter()
+            .map(|font| normalize(&font.family))
+            .filter(|family| !family.is_empty() && name.starts_with(family.as_str()))
+            .max_by_key(|family| family.len())?;
+        let (weight, italic) = parse_style(&name[family.len()..]);
+
+        self.fonts
+            .iter()
+            .filter(|font| normalize(&font.family) == family)
+            .min_by_key(|font| (font.italic != italic, font.weight.abs_diff(weight)))
+    }
+
+    /// The data for `name`, or the first bundled font if nothing matches.
+    pub fn load(&mut self, name: &str) -> &'static [u8] {
+        let source = self.query(name).map(|font| font.source.clone());
+        let path = match source {
+            Some(FontSource::Path(path)) => path,
+            Some(FontSource::Bundled(data)) => return data,
+            None => return BUNDLED_FONTS[0],
+        };
+
+        if let Some(data) = self.loaded.get(&path) {
+            return data;
+        }
+
+        match std::fs::read(&path) {
+            Ok(data) => {
+                let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+                self.loaded.insert(path, data);
+                data
+            }
+            //Removed since the scan.
+            Err(_) => BUNDLED_FONTS[0],
+        }
+    }
+
+    /// Rasterize the font closest to `name` at `size` pixels per em.
+    ///
+    /// # Safety
+    /// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+    #[cfg(feature = "freetype")]
+    pub unsafe fn load_font(&mut self, rd: &Renderer, name: &str, size: u32) -> Atlas {
+        load_font(rd, self.load(name), size)
+    }
+}
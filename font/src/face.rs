@@ -0,0 +1,83 @@
+use crate::*;
+use freetype::Library;
+use std::collections::HashMap;
+
+/// One loaded face with an atlas for each size it's been drawn at.
+///
+/// ```rs
+/// let mut font = Font::new(include_bytes!("../JetBrainsMono.ttf"));
+/// font.atlas(&rd, 32).draw_text(&mut rd, "Heading", 25.0, 200.0, color);
+/// font.atlas(&rd, 12).draw_text(&mut rd, "Body text", 25.0, 150.0, color);
+/// ```
+pub struct Font {
+    pub data: &'static [u8],
+    /// DPI scale applied to every requested size, e.g. `window.get_content_scale()`.
+    pub scale: f32,
+    pub mode: Mode,
+    /// Faces tried in order when `data` doesn't have a character.
+    pub fallbacks: Vec<&'static [u8]>,
+    /// Design axes for variable fonts, see [`Variation`].
+    pub variations: Vec<Variation>,
+    /// Hinting, antialiasing and emboldening of every atlas, see [`FontOptions`].
+    pub options: FontOptions,
+    pub atlases: HashMap<u32, Atlas>,
+    lib: Library,
+}
+
+impl Font {
+    pub fn new(data: &'static [u8]) -> Self {
+        Self {
+            data,
+            scale: 1.0,
+            mode: Mode::Coverage,
+            fallbacks: Vec::new(),
+            variations: Vec::new(),
+            options: FontOptions::default(),
+            atlases: HashMap::new(),
+            lib: Library::init().unwrap(),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_fallback(mut self, data: &'static [u8]) -> Self {
+        self.fallbacks.push(data);
+        self
+    }
+
+    pub fn with_variations(mut self, variations: &[Variation]) -> Self {
+        self.variations = variations.to_vec();
+        self
+    }
+
+    pub fn with_options(mut self, options: FontOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the atlas for `size` pixels, before DPI scaling. It's created on first use.
+    ///
+    /// # Safety
+    /// Uploads new atlases, the GL context of `rd` has to be current on this thread.
+    pub unsafe fn atlas(&mut self, rd: &Renderer, size: u32) -> &mut Atlas {
+        let size = (size as f32 * self.scale).round().max(1.0) as u32;
+        self.atlases.entry(size).or_insert_with(|| {
+            let mut atlas = Atlas::empty(&self.lib, self.data, size, self.mode, &self.variations);
+            atlas.options = self.options;
+            for fallback in &self.fallbacks {
+                atlas.add_fallback(&self.lib, fallback);
+            }
+            atlas.reload();
+            atlas.upload(rd.gl);
+            atlas
+        })
+    }
+}
@@ -0,0 +1,232 @@
+use crate::*;
+#[cfg(feature = "freetype")]
+use freetype::Library;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Style {
+    #[default]
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+}
+
+impl Style {
+    pub const ALL: [Style; 4] = [
+        Style::Regular,
+        Style::Bold,
+        Style::Italic,
+        Style::BoldItalic,
+    ];
+
+    pub const fn new(bold: bool, italic: bool) -> Self {
+        match (bold, italic) {
+            (false, false) => Style::Regular,
+            (true, false) => Style::Bold,
+            (false, true) => Style::Italic,
+            (true, true) => Style::BoldItalic,
+        }
+    }
+
+    pub const fn is_bold(self) -> bool {
+        matches!(self, Style::Bold | Style::BoldItalic)
+    }
+
+    pub const fn is_italic(self) -> bool {
+        matches!(self, Style::Italic | Style::BoldItalic)
+    }
+
+    /// Index into [`FontFamily::atlases`].
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Fake styles for families that don't ship a face for them, applied to the outline before it's
+/// rasterized. Advances don't change, so bold text stays on the same grid as regular text.
+/// That suits monospace fonts, proportional fonts look cramped and need a real bold face.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Synthetic {
+    /// Expand the outline by 1/24 of an em.
+    ///https://freetype.org/freetype2/docs/reference/ft2-outline_processing.html#ft_outline_embolden
+    pub bold: bool,
+    /// Shear the outline by 12 degrees.
+    pub oblique: bool,
+}
+
+impl Atlas {
+    /// Rasterize every glyph again with `synthetic` applied.
+    #[cfg(feature = "freetype")]
+    pub fn set_synthetic(&mut self, synthetic: Synthetic) {
+        self.synthetic = synthetic;
+        self.reload();
+    }
+}
+
+/// An atlas for each [`Style`] of one family.
+///
+/// ```rs
+/// let mut family = load_family(&rd, 24, regular, Some(bold), None, None);
+/// family.draw_text(&mut rd, "Warning:", 25.0, 200.0, Style::Bold, yellow);
+/// ```
+#[derive(Debug)]
+pub struct FontFamily {
+    /// Indexed by [`Style::index`].
+    pub atlases: [Atlas; 4],
+}
+
+impl FontFamily {
+    pub fn get(&self, style: Style) -> &Atlas {
+        &self.atlases[style.index()]
+    }
+
+    pub fn get_mut(&mut self, style: Style) -> &mut Atlas {
+        &mut self.atlases[style.index()]
+    }
+
+    pub fn draw_text(
+        &mut self,
+        rd: &mut Renderer,
+        text: &str,
+        x: f32,
+        y: f32,
+        style: Style,
+        color: Vec4,
+    ) {
+        self.get_mut(style).draw_text(rd, text, x, y, color);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_text_with(
+        &mut self,
+        rd: &mut Renderer,
+        text: &str,
+        x: f32,
+        y: f32,
+        style: Style,
+        color: Vec4,
+        options: TextOptions,
+    ) {
+        self.get_mut(style)
+            .draw_text_with(rd, text, x, y, color, options);
+    }
+
+    /// Mixed styles in one block of text, give each span a style with [`Span::with_style`].
+    pub fn draw_rich_text(
+        &mut self,
+        rd: &mut Renderer,
+        spans: &[Span],
+        x: f32,
+        y: f32,
+        options: TextOptions,
+    ) -> Vec<Batch> {
+        draw_rich_text(rd, &mut self.atlases, spans, x, y, options)
+    }
+
+    pub fn layout(&self, text: &str, style: Style, options: TextOptions) -> Vec<Line> {
+        self.get(style).layout(text, options)
+    }
+
+    pub fn measure(&self, text: &str, style: Style) -> TextMetrics {
+        self.get(style).measure(text)
+    }
+
+    pub fn measure_with(&self, text: &str, style: Style, options: TextOptions) -> TextMetrics {
+        self.get(style).measure_with(text, options)
+    }
+}
+
+#[cfg(feature = "freetype")]
+impl FontFamily {
+    /// Rasterize every style at `size` pixels per em without touching the GPU. Styles without a
+    /// font are synthesized from the closest one that has a font: bold italic from bold, then
+    /// italic, then regular. The others from regular.
+    pub fn new(
+        lib: &Library,
+        size: u32,
+        mode: Mode,
+        regular: &'static [u8],
+        bold: Option<&'static [u8]>,
+        italic: Option<&'static [u8]>,
+        bold_italic: Option<&'static [u8]>,
+    ) -> Self {
+        Self::new_with_options(
+            lib,
+            size,
+            mode,
+            regular,
+            bold,
+            italic,
+            bold_italic,
+            FontOptions::default(),
+        )
+    }
+
+    /// Same as [`FontFamily::new`] with every style rasterized with `options`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_options(
+        lib: &Library,
+        size: u32,
+        mode: Mode,
+        regular: &'static [u8],
+        bold: Option<&'static [u8]>,
+        italic: Option<&'static [u8]>,
+        bold_italic: Option<&'static [u8]>,
+        options: FontOptions,
+    ) -> Self {
+        let fonts = [Some(regular), bold, italic, bold_italic];
+
+        let atlases = Style::ALL.map(|style| {
+            let (source, font) = [
+                style,
+                Style::new(style.is_bold(), false),
+                Style::new(false, style.is_italic()),
+                Style::Regular,
+            ]
+            .into_iter()
+            .find_map(|source| Some((source, fonts[source.index()]?)))
+            .unwrap();
+
+            //Set before anything is rasterized so each glyph is only rasterized once.
+            let mut atlas = Atlas::empty(lib, font, size, mode, &[]);
+            atlas.synthetic = Synthetic {
+                bold: style.is_bold() && !source.is_bold(),
+                oblique: style.is_italic() && !source.is_italic(),
+            };
+            atlas.options = options;
+            atlas.reload();
+            atlas
+        });
+
+        Self { atlases }
+    }
+}
+
+/// Rasterize and upload a family, see [`FontFamily::new`].
+///
+/// # Safety
+/// Uploads the atlases, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_family(
+    rd: &Renderer,
+    size: u32,
+    regular: &'static [u8],
+    bold: Option<&'static [u8]>,
+    italic: Option<&'static [u8]>,
+    bold_italic: Option<&'static [u8]>,
+) -> FontFamily {
+    let lib = Library::init().unwrap();
+    let mut family = FontFamily::new(
+        &lib,
+        size,
+        Mode::Coverage,
+        regular,
+        bold,
+        italic,
+        bold_italic,
+    );
+    for atlas in &mut family.atlases {
+        atlas.upload(rd.gl);
+    }
+    family
+}
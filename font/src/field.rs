@@ -0,0 +1,596 @@
+use crate::*;
+use glfw::{Action, Key, Modifiers, MouseButton, WindowEvent};
+use std::ops::Range;
+
+/// Text and selection from before an edit.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    text: String,
+    caret: usize,
+    anchor: usize,
+}
+
+/// Runs of typing or deleting are undone in one step, everything else on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Type,
+    Delete,
+    Other,
+}
+
+/// An editable box of text with a caret, mouse and keyboard selection and undo history.
+///
+/// ```rs
+/// let mut field = TextField::new().with_multiline(400.0);
+/// window.set_char_polling(true);
+/// window.set_mouse_button_polling(true);
+/// window.set_cursor_pos_polling(true);
+///
+/// for (_, event) in glfw::flush_messages(&events) {
+///     field.handle_event(&mut window, &atlas, &event, 25.0, 600.0);
+/// }
+/// field.draw(&mut rd, &mut atlas, 25.0, 600.0);
+/// ```
+///
+/// The selection and caret are drawn with [`Renderer::quad`], which samples the solid block of
+/// the atlas, so the whole field is drawn with the atlas texture bound.
+#[derive(Debug, Clone)]
+pub struct TextField {
+    pub text: String,
+    /// Byte index of the caret.
+    pub caret: usize,
+    /// Byte index of the other end of the selection, the same as `caret` when nothing is selected.
+    pub anchor: usize,
+    /// Accept line breaks and wrap at `width`. Single line fields turn pasted line breaks into spaces.
+    pub multiline: bool,
+    /// Width of the area that can be clicked, multi-line fields also wrap at it.
+    pub width: f32,
+    /// Keyboard input only goes to focused fields. Clicking inside focuses, clicking outside doesn't.
+    pub focused: bool,
+    pub options: TextOptions,
+    pub color: Vec4,
+    pub selection_color: Vec4,
+    pub caret_color: Vec4,
+    /// How far single line fields are scrolled to the left to keep the caret visible.
+    pub scroll: f32,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: Option<Edit>,
+    /// Horizontal position the caret returns to when moving through shorter lines.
+    goal_x: Option<f32>,
+    /// The left button was pressed inside the field and hasn't been released yet.
+    dragging: bool,
+}
+
+impl Default for TextField {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            caret: 0,
+            anchor: 0,
+            multiline: false,
+            width: 200.0,
+            focused: false,
+            options: TextOptions::default(),
+            color: hex(0xdcdcdc),
+            selection_color: hex(0x264f78),
+            caret_color: hex(0xdcdcdc),
+            scroll: 0.0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+            goal_x: None,
+            dragging: false,
+        }
+    }
+}
+
+impl TextField {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start with `text` and the caret at the end.
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.to_string();
+        self.caret = text.len();
+        self.anchor = text.len();
+        self
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_multiline(mut self, width: f32) -> Self {
+        self.multiline = true;
+        self.width = width;
+        self
+    }
+
+    /// Byte range of the selected text, empty when nothing is selected.
+    pub fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection()]
+    }
+
+    /// Layout options with wrapping for multi-line fields.
+    pub fn layout_options(&self) -> TextOptions {
+        TextOptions {
+            max_width: self.multiline.then_some(self.width),
+            ..self.options
+        }
+    }
+
+    /// Move the caret, the anchor stays put when `select` is set.
+    pub fn set_caret(&mut self, index: usize, select: bool) {
+        let mut index = index.min(self.text.len());
+        while !self.text.is_char_boundary(index) {
+            index -= 1;
+        }
+
+        self.caret = index;
+        if !select {
+            self.anchor = index;
+        }
+        self.last_edit = None;
+        self.goal_x = None;
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = 0;
+        self.set_caret(self.text.len(), true);
+    }
+
+    /// Replace the selection with `text`, typing over it if nothing is selected.
+    pub fn insert(&mut self, text: &str) {
+        let text = self.clean(text);
+        let edit = if text.chars().count() == 1 {
+            Edit::Type
+        } else {
+            Edit::Other
+        };
+        self.replace(self.selection(), &text, edit);
+    }
+
+    /// Line breaks as '\n', or spaces in a single line field.
+    fn clean(&self, text: &str) -> String {
+        let text = text.replace("\r\n", "\n");
+        if self.multiline {
+            text
+        } else {
+            text.replace('\n', " ")
+        }
+    }
+
+    /// Delete the selection, or the character or word before the caret.
+    pub fn backspace(&mut self, word: bool) {
+        let range = match self.selection() {
+            range if !range.is_empty() => range,
+            _ if word => previous_word(&self.text, self.caret)..self.caret,
+            _ => previous_char(&self.text, self.caret)..self.caret,
+        };
+        self.replace(range, "", Edit::Delete);
+    }
+
+    /// Delete the selection, or the character or word after the caret.
+    pub fn delete(&mut self, word: bool) {
+        let range = match self.selection() {
+            range if !range.is_empty() => range,
+            _ if word => self.caret..next_word(&self.text, self.caret),
+            _ => self.caret..next_char(&self.text, self.caret),
+        };
+        self.replace(range, "", Edit::Delete);
+    }
+
+    fn replace(&mut self, range: Range<usize>, text: &str, edit: Edit) {
+        if range.is_empty() && text.is_empty() {
+            return;
+        }
+
+        if edit == Edit::Other || self.last_edit != Some(edit) {
+            self.undo.push(self.snapshot());
+        }
+        self.redo.clear();
+
+        self.text.replace_range(range.clone(), text);
+        self.set_caret(range.start + text.len(), false);
+        self.last_edit = Some(edit);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            caret: self.caret,
+            anchor: self.anchor,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.anchor = snapshot.anchor;
+        self.set_caret(snapshot.caret, true);
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            self.redo.push(self.snapshot());
+            self.restore(snapshot);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            self.undo.push(self.snapshot());
+            self.restore(snapshot);
+        }
+    }
+
+    /// Move one character or word. Without `select` an existing selection collapses to the side
+    /// being moved towards.
+    pub fn move_left(&mut self, select: bool, word: bool) {
+        let selection = self.selection();
+        let index = if !select && !word && !selection.is_empty() {
+            selection.start
+        } else if word {
+            previous_word(&self.text, self.caret)
+        } else {
+            previous_char(&self.text, self.caret)
+        };
+        self.set_caret(index, select);
+    }
+
+    pub fn move_right(&mut self, select: bool, word: bool) {
+        let selection = self.selection();
+        let index = if !select && !word && !selection.is_empty() {
+            selection.end
+        } else if word {
+            next_word(&self.text, self.caret)
+        } else {
+            next_char(&self.text, self.caret)
+        };
+        self.set_caret(index, select);
+    }
+
+    /// Move to the start of the line the caret is on, wrapped lines count as their own line.
+    pub fn move_home(&mut self, atlas: &Atlas, select: bool) {
+        let lines = atlas.layout(&self.text, self.layout_options());
+        let line = &lines[line_at(&lines, self.caret)];
+        self.set_caret(line.range.start, select);
+    }
+
+    pub fn move_end(&mut self, atlas: &Atlas, select: bool) {
+        let lines = atlas.layout(&self.text, self.layout_options());
+        let line = &lines[line_at(&lines, self.caret)];
+        self.set_caret(line.range.end, select);
+    }
+
+    /// Move `lines` up (negative) or down, keeping the caret's horizontal position.
+    /// Moving past the first or last line goes to the start or end of the text.
+    pub fn move_vertical(&mut self, atlas: &Atlas, lines: i32, select: bool) {
+        let options = self.layout_options();
+        let layout = atlas.layout(&self.text, options);
+        let caret = atlas.caret(&self.text, self.caret, options);
+        let goal_x = self.goal_x.unwrap_or(caret.x);
+
+        let target = caret.line as i32 + lines;
+        let index = if target < 0 {
+            0
+        } else if target as usize >= layout.len() {
+            self.text.len()
+        } else {
+            hit_line(&self.text, &layout[target as usize], goal_x).0
+        };
+
+        self.set_caret(index, select);
+        self.goal_x = Some(goal_x);
+    }
+
+    /// Scroll a single line field just enough to show the caret. [`TextField::draw`] does this
+    /// every frame.
+    pub fn scroll_to_caret(&mut self, atlas: &Atlas) {
+        if self.multiline {
+            self.scroll = 0.0;
+            return;
+        }
+
+        let options = self.layout_options();
+        let caret = atlas.caret(&self.text, self.caret, options);
+        let right = caret.x + caret.width;
+        if right - self.scroll > self.width {
+            self.scroll = right - self.width;
+        }
+        if caret.x < self.scroll {
+            self.scroll = caret.x;
+        }
+
+        //Don't leave empty space on the right after deleting.
+        let end = atlas.measure_with(&self.text, options).width + caret.width;
+        self.scroll = self.scroll.min(end - self.width).max(0.0);
+    }
+
+    /// The text under `point` relative to where the field is drawn.
+    fn hit(&self, atlas: &Atlas, point: Vec2) -> Hit {
+        let point = Vec2::new(point.x + self.scroll, point.y);
+        atlas.hit_test(&self.text, point, self.layout_options())
+    }
+
+    /// Put the caret under `point`, relative to where the field is drawn. `extend` keeps the anchor.
+    pub fn click(&mut self, atlas: &Atlas, point: Vec2, extend: bool) {
+        let hit = self.hit(atlas, point);
+        self.set_caret(hit.index, extend);
+        self.dragging = true;
+    }
+
+    /// Extend the selection to `point` while the button is held after a [`TextField::click`].
+    pub fn drag(&mut self, atlas: &Atlas, point: Vec2) {
+        if self.dragging {
+            let hit = self.hit(atlas, point);
+            self.set_caret(hit.index, true);
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.dragging = false;
+    }
+
+    /// Is `point` inside the field, relative to where it's drawn.
+    pub fn contains(&self, atlas: &Atlas, point: Vec2) -> bool {
+        let options = self.layout_options();
+        let metrics = atlas.measure_with(&self.text, options);
+        let top = metrics.baseline;
+        let bottom = top - metrics.height.max(atlas.line_advance(options));
+        (0.0..=self.width).contains(&point.x) && (bottom..=top).contains(&point.y)
+    }
+
+    pub fn copy(&self, window: &mut glfw::Window) {
+        if !self.selection().is_empty() {
+            window.set_clipboard_string(self.selected_text());
+        }
+    }
+
+    pub fn cut(&mut self, window: &mut glfw::Window) {
+        if !self.selection().is_empty() {
+            self.copy(window);
+            self.replace(self.selection(), "", Edit::Other);
+        }
+    }
+
+    pub fn paste(&mut self, window: &glfw::Window) {
+        if let Some(text) = window.get_clipboard_string() {
+            self.replace(self.selection(), &self.clean(&text), Edit::Other);
+        }
+    }
+
+    /// Update the field from a window event, `[x, y]` is where it's drawn. Returns `true` if the
+    /// field used the event. Enter in a single line field isn't used so it can submit a form.
+    ///
+    /// Needs char, mouse button and cursor position polling on the window.
+    pub fn handle_event(
+        &mut self,
+        window: &mut glfw::Window,
+        atlas: &Atlas,
+        event: &WindowEvent,
+        x: f32,
+        y: f32,
+    ) -> bool {
+        match *event {
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Press, mods) => {
+                let point = cursor(window, x, y);
+                self.focused = self.contains(atlas, point);
+                if self.focused {
+                    self.click(atlas, point, mods.contains(Modifiers::Shift));
+                }
+                self.focused
+            }
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Release, _) => {
+                let dragging = self.dragging;
+                self.release();
+                dragging
+            }
+            WindowEvent::CursorPos(..) if self.dragging => {
+                self.drag(atlas, cursor(window, x, y));
+                true
+            }
+            WindowEvent::Char(c) if self.focused && !c.is_control() => {
+                self.insert(c.encode_utf8(&mut [0; 4]));
+                true
+            }
+            WindowEvent::Key(key, _, Action::Press | Action::Repeat, mods) if self.focused => {
+                self.handle_key(window, atlas, key, mods)
+            }
+            _ => false,
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        window: &mut glfw::Window,
+        atlas: &Atlas,
+        key: Key,
+        mods: Modifiers,
+    ) -> bool {
+        let shift = mods.contains(Modifiers::Shift);
+        let control = mods.contains(Modifiers::Control);
+
+        match key {
+            Key::Left => self.move_left(shift, control),
+            Key::Right => self.move_right(shift, control),
+            Key::Up => self.move_vertical(atlas, -1, shift),
+            Key::Down => self.move_vertical(atlas, 1, shift),
+            Key::Home if control => self.set_caret(0, shift),
+            Key::End if control => self.set_caret(self.text.len(), shift),
+            Key::Home => self.move_home(atlas, shift),
+            Key::End => self.move_end(atlas, shift),
+            Key::Backspace => self.backspace(control),
+            Key::Delete => self.delete(control),
+            Key::Enter | Key::KpEnter if self.multiline => self.insert("\n"),
+            Key::A if control => self.select_all(),
+            Key::C if control => self.copy(window),
+            Key::X if control => self.cut(window),
+            Key::V if control => self.paste(window),
+            Key::Z if control && shift => self.redo(),
+            Key::Z if control => self.undo(),
+            Key::Y if control => self.redo(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draw the selection, text and caret with the first baseline at `[x, y]`. Anything outside
+    /// `width` is cut off.
+    pub fn draw(&mut self, rd: &mut Renderer, atlas: &mut Atlas, x: f32, y: f32) {
+        self.scroll_to_caret(atlas);
+        let start = rd.vertices.len();
+        let (left, right) = (x, x + self.width);
+        let x = x - self.scroll;
+
+        let options = self.layout_options();
+        let descender = (atlas.descender * options.scale).round();
+        let height = ((atlas.ascender - atlas.descender) * options.scale).round();
+
+        let selection = self.selection();
+        if !selection.is_empty() {
+            for line in atlas.layout(&self.text, options) {
+                for (left, right) in selection_spans(&self.text, &line, selection.clone()) {
+                    rd.quad(
+                        x + left,
+                        y + line.y + descender,
+                        right - left,
+                        height,
+                        self.selection_color,
+                    );
+                }
+            }
+        }
+
+        atlas.draw_text_with(rd, &self.text, x, y, self.color, options);
+
+        if self.focused {
+            let caret = atlas.caret(&self.text, self.caret, options);
+            rd.quad(
+                x + caret.x,
+                y + caret.y,
+                caret.width,
+                caret.height,
+                self.caret_color,
+            );
+        }
+
+        clip_x(&mut rd.vertices[start..], left, right);
+    }
+}
+
+/// Cut the quads in `vertices` off at `left` and `right`, texture coordinates included so the
+/// glyphs aren't squashed. Quads entirely outside end up with no width.
+pub(crate) fn clip_x(vertices: &mut [Vertex], left: f32, right: f32) {
+    for quad in vertices.chunks_exact_mut(6) {
+        //Every quad is axis aligned, each corner is on one of two edges.
+        let (mut x0, mut x1) = (f32::MAX, f32::MIN);
+        let (mut u0, mut u1) = (0.0, 0.0);
+        for vertex in quad.iter() {
+            if vertex.position.x < x0 {
+                (x0, u0) = (vertex.position.x, vertex.uv.x);
+            }
+            if vertex.position.x > x1 {
+                (x1, u1) = (vertex.position.x, vertex.uv.x);
+            }
+        }
+
+        for vertex in quad {
+            let x = vertex.position.x.clamp(left, right);
+            if x1 > x0 {
+                vertex.uv.x = u0 + (u1 - u0) * (x - x0) / (x1 - x0);
+            }
+            vertex.position.x = x;
+        }
+    }
+}
+
+/// Cursor position relative to `[x, y]` in framebuffer pixels, with the y axis going up.
+fn cursor(window: &glfw::Window, x: f32, y: f32) -> Vec2 {
+    let (cursor_x, cursor_y) = window.get_cursor_pos();
+    let (width, height) = window.get_size();
+    let (fb_width, fb_height) = window.get_framebuffer_size();
+    let scale_x = fb_width as f32 / width.max(1) as f32;
+    let scale_y = fb_height as f32 / height.max(1) as f32;
+
+    Vec2::new(
+        cursor_x as f32 * scale_x - x,
+        (height as f32 - cursor_y as f32) * scale_y - y,
+    )
+}
+
+/// Nonspacing marks move and get deleted with the character they're on, see [`is_mark`].
+fn previous_char(text: &str, index: usize) -> usize {
+    text[..index]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_mark(c))
+        .map_or(0, |(i, _)| i)
+}
+
+fn next_char(text: &str, index: usize) -> usize {
+    let mut chars = text[index..].char_indices().skip(1);
+    chars
+        .find(|&(_, c)| !is_mark(c))
+        .map_or(text.len(), |(i, _)| index + i)
+}
+
+/// Words are runs of letters and digits or runs of punctuation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+/// Start of the word before `index`, skipping whitespace.
+pub(crate) fn previous_word(text: &str, index: usize) -> usize {
+    let mut chars = text[..index]
+        .char_indices()
+        .rev()
+        .skip_while(|(_, c)| class(*c) == Class::Space)
+        .peekable();
+    let Some(&(mut start, first)) = chars.peek() else {
+        return 0;
+    };
+    for (i, c) in chars {
+        if class(c) != class(first) {
+            break;
+        }
+        start = i;
+    }
+    start
+}
+
+/// End of the word after `index`, skipping whitespace.
+pub(crate) fn next_word(text: &str, index: usize) -> usize {
+    let mut chars = text[index..]
+        .char_indices()
+        .skip_while(|(_, c)| class(*c) == Class::Space)
+        .peekable();
+    let Some(&(_, first)) = chars.peek() else {
+        return text.len();
+    };
+    for (i, c) in chars {
+        if class(c) != class(first) {
+            return index + i;
+        }
+    }
+    text.len()
+}
@@ -1,36 +1,285 @@
 use crate::*;
+#[cfg(feature = "freetype")]
 use freetype::face::LoadFlag;
-use freetype::ffi::FT_LCD_FILTER_DEFAULT;
-use freetype::{Error, FtResult, Library, RenderMode};
+#[cfg(feature = "freetype")]
+use freetype::{bitmap::PixelMode, Face, Library};
 
-use freetype_sys::{
-    FT_Err_Ok, FT_Library_SetLcdFilter, FT_Load_Char, FT_LOAD_RENDER, FT_RENDER_MODE_SDF,
-};
 pub use glow::HasContext;
 
-const FONT_SIZE: u32 = 48;
+/// What the atlas texture stores for each glyph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Antialiased coverage, drawn with `text.frag`.
+    #[default]
+    Coverage,
+    /// Signed distance field, 128 is the outline and values above it are inside the glyph.
+    /// Stays sharp when scaled up, drawn with `sdf.frag`.
+    ///https://freetype.org/freetype2/docs/reference/ft2-properties.html#spread
+    Sdf,
+    /// Subpixel antialiasing, one coverage value per color channel.
+    /// Drawn with `lcd.frag`, only looks right when the text isn't scaled or rotated.
+    Lcd {
+        order: PixelOrder,
+        filter: LcdFilter,
+    },
+    /// Straight alpha RGBA, only used for the page that holds color glyphs, see [`Atlas::color`].
+    Color,
+}
+
+impl Mode {
+    /// Bytes per pixel in the atlas.
+    pub const fn channels(self) -> i32 {
+        match self {
+            Mode::Coverage | Mode::Sdf => 1,
+            Mode::Lcd { .. } => 3,
+            Mode::Color => 4,
+        }
+    }
+
+    /// Texture format of the atlas.
+    pub const fn format(self) -> u32 {
+        match self {
+            Mode::Coverage | Mode::Sdf => glow::RED,
+            Mode::Lcd { .. } => glow::RGB,
+            Mode::Color => glow::RGBA,
+        }
+    }
+}
+
+/// Texture coordinates of a glyph, (u0, v0) is the top left corner of the bitmap.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UvRect {
+    pub u0: f32,
+    pub v0: f32,
+    pub u1: f32,
+    pub v1: f32,
+}
+
+impl UvRect {
+    /// Normalize a `w` x `h` rectangle at `[x, y]` in a texture of size `width` x `height`.
+    pub fn new(x: i32, y: i32, w: i32, h: i32, width: i32, height: i32) -> Self {
+        Self {
+            u0: x as f32 / width as f32,
+            v0: y as f32 / height as f32,
+            u1: (x + w) as f32 / width as f32,
+            v1: (y + h) as f32 / height as f32,
+        }
+    }
+}
 
 ///https://learnopengl.com/img/in-practice/glyph.png
 ///https://en.wikibooks.org/wiki/OpenGL_Programming/Modern_OpenGL_Tutorial_Text_Rendering_02
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Glyph {
     /// Padding
     pub advance: Vec2,
     pub width: f32,
     pub height: f32,
     pub bearing: Vec2,
-    /// X offset of glyph in texture.
-    pub uv: f32,
-    //TODO: Remove
-    pub buffer: Vec<u8>,
+    pub uv: UvRect,
+    /// Top left corner of the glyph in the texture, in pixels.
+    pub x: i32,
+    pub y: i32,
+    /// Lives in the color page and is drawn without tinting, see [`COLOR_UV_OFFSET`].
+    pub color: bool,
 }
 
-#[derive(Debug)]
-pub struct Atlas {
+#[derive(Debug, Clone, Copy)]
+struct Shelf {
+    y: i32,
+    height: i32,
+    /// X offset of the next free column.
+    x: i32,
+}
+
+///Shelf packer. Rectangles are placed left to right on rows,
+///a new row is opened underneath the last one when nothing fits.
+///https://jvernay.fr/en/blog/skyline-2d-packer/implementation/
+#[derive(Debug, Clone)]
+pub struct Packer {
     pub width: i32,
     pub height: i32,
-    pub texture: glow::NativeTexture,
-    pub glyphs: [Glyph; 128],
+    shelves: Vec<Shelf>,
+}
+
+impl Packer {
+    /// Space left between rectangles so linear filtering doesn't bleed neighbours into each other.
+    pub const PADDING: i32 = 1;
+
+    pub const fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Returns the top left corner of a free `width` x `height` rectangle.
+    /// `None` when the atlas is full, the caller should [`Packer::grow`] it or start a new page.
+    pub fn pack(&mut self, width: i32, height: i32) -> Option<(i32, i32)> {
+        let w = width + Self::PADDING;
+        let h = height + Self::PADDING;
+
+        if w > self.width {
+            return None;
+        }
+
+        //Pick the shelf that wastes the least vertical space.
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= h && shelf.x + w <= self.width)
+            .min_by_key(|shelf| shelf.height - h);
+
+        if let Some(shelf) = best {
+            let position = (shelf.x, shelf.y);
+            shelf.x += w;
+            return Some(position);
+        }
+
+        let y = self.shelves.last().map(|s| s.y + s.height).unwrap_or(0);
+        if y + h > self.height {
+            return None;
+        }
+
+        self.shelves.push(Shelf { y, height: h, x: w });
+        Some((0, y))
+    }
+
+    /// Extends the packer downwards, existing rectangles keep their position.
+    pub fn grow(&mut self, height: i32) {
+        debug_assert!(height >= self.height);
+        self.height = height;
+    }
+}
+
+/// Key of the solid white block used to draw underlines and boxes with the text shader.
+pub const SOLID: GlyphKey = GlyphKey {
+    face: usize::MAX,
+    id: 0,
+    subpixel: 0,
+};
+
+/// Identifies a glyph in the atlas, `face` is the index into [`Atlas::faces`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub face: usize,
+    pub id: u32,
+    /// Rasterized `subpixel / Atlas::subpixel` of a pixel to the right, see [`Atlas::set_subpixel`].
+    pub subpixel: u8,
+}
+
+/// A face in the fallback chain.
+#[derive(Debug)]
+pub struct FontFace {
+    /// `None` for baked atlases, they can only draw the glyphs they were saved with.
+    #[cfg(feature = "freetype")]
+    pub face: Option<Face<&'static [u8]>>,
+    /// Multiplier from the bitmap strike to the atlas size for fonts that only have fixed sizes.
+    #[cfg(feature = "freetype")]
+    pub bitmap_scale: f32,
+    pub shaper: Shaper,
+}
+
+impl FontFace {
+    /// Open `font` at `size` pixels per em.
+    #[cfg(feature = "freetype")]
+    pub fn new(lib: &Library, font: &'static [u8], size: u32, mode: Mode) -> Self {
+        //The filter is global to the library, so atlases sharing one should use the same filter.
+        //FreeType built without FT_CONFIG_OPTION_SUBPIXEL_RENDERING has no filters,
+        //it still renders LCD glyphs with Harmony LCD, just unfiltered.
+        if let Mode::Lcd { filter, .. } = mode {
+            match lib.set_lcd_filter(filter.into()) {
+                Ok(()) | Err(freetype::Error::UnimplementedFeature) => {}
+                Err(error) => panic!("FT_Library_SetLcdFilter failed: {error}"),
+            }
+        }
+
+        let mut face = lib.new_memory_face2(font, 0).unwrap();
+        let bitmap_scale = if face.is_scalable() {
+            face.set_pixel_sizes(0, size).unwrap();
+            1.0
+        } else {
+            select_strike(&mut face, size)
+        };
+
+        Self {
+            face: Some(face),
+            bitmap_scale,
+            shaper: Shaper::new(font, size as f32),
+        }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.shaper.face.glyph_index(c).is_some()
+    }
+}
+
+#[derive(Debug)]
+pub struct Atlas {
+    /// Pixels per em the glyphs were rasterized at.
+    pub size: u32,
+    pub mode: Mode,
+    /// Baseline to baseline distance, `ascender - descender + line_gap`.
+    pub line_height: f32,
+    /// Distance from the baseline to the top of the line.
+    pub ascender: f32,
+    /// Distance from the baseline to the bottom of the line, negative below the baseline.
+    pub descender: f32,
+    /// Extra space the font wants between the bottom of one line and the top of the next.
+    pub line_gap: f32,
+    /// Font units per em of the primary face, the metrics above are already scaled to pixels.
+    pub units_per_em: u16,
+    /// Glyphs rasterized so far, they reach the GPU on the next [`Atlas::upload`].
+    pub bitmap: AtlasBitmap,
+    /// `None` until the first upload.
+    pub texture: Option<glow::NativeTexture>,
+    /// RGBA page for emoji and other color glyphs, `None` until the first one is drawn.
+    pub color: Option<AtlasBitmap>,
+    pub color_texture: Option<glow::NativeTexture>,
+    /// The primary face followed by the fallbacks, in the order they're tried.
+    pub faces: Vec<FontFace>,
+    /// Glyph drawn in place of characters none of the faces have.
+    pub fallback: GlyphKey,
+    /// Design axes every face was rasterized at, empty for the defaults.
+    pub variations: Vec<Variation>,
+    /// Emboldening and slant applied to every glyph, see [`FontFamily`].
+    pub synthetic: Synthetic,
+    /// Horizontal offsets each glyph is rasterized at, 1 puts every glyph on a whole pixel.
+    pub subpixel: u8,
+    /// Hinting, antialiasing and emboldening, see [`Atlas::set_options`].
+    pub options: FontOptions,
+}
+
+/// Per call settings for [`Atlas::draw_text_with`].
+#[derive(Debug, Clone, Copy)]
+pub struct TextOptions {
+    /// Apply the font's `kern` feature. Turn off for monospace grids.
+    pub kerning: bool,
+    /// Wrap at word boundaries so no line is wider than this.
+    pub max_width: Option<f32>,
+    /// Alignment inside `max_width`, or inside the widest line if there is no limit.
+    pub align: Align,
+    /// Multiplier on the size the atlas was rasterized at. Anything but 1.0 will look blurry
+    /// unless the atlas is [`Mode::Sdf`].
+    pub scale: f32,
+    /// Multiplier on [`Atlas::line_height`].
+    pub line_spacing: f32,
+    /// Distance between tab stops, measured from the start of each line.
+    pub tab_width: TabWidth,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            kerning: true,
+            max_width: None,
+            align: Align::Left,
+            scale: 1.0,
+            line_spacing: 1.0,
+            tab_width: TabWidth::Spaces(4),
+        }
+    }
 }
 
 impl Atlas {
@@ -38,110 +287,583 @@ impl Atlas {
     //It does seem like the projection is squishing the font.
     //The big letters like j seem fine but letters like e are squished.
     //I should probably align everything in the texture and save myself the trouble.
-    pub fn draw_text(&self, rd: &mut Renderer, text: &str, mut x: f32, mut y: f32, color: Vec4) {
-        let start_x = x;
-        for c in text.chars() {
-            let ch = match self.glyphs.get(c as usize) {
-                Some(ch) => ch,
-                None => &self.glyphs['?' as usize],
-            };
-
-            if c == '\n' {
-                y -= self.height as f32;
-                x = start_x;
+    pub fn draw_text(&mut self, rd: &mut Renderer, text: &str, x: f32, y: f32, color: Vec4) {
+        self.draw_text_with(rd, text, x, y, color, TextOptions::default());
+    }
+
+    /// `[x, y]` is the first baseline of the left edge of the box, set [`TextOptions::max_width`]
+    /// to wrap.
+    pub fn draw_text_with(
+        &mut self,
+        rd: &mut Renderer,
+        text: &str,
+        x: f32,
+        y: f32,
+        color: Vec4,
+        options: TextOptions,
+    ) {
+        for line in self.layout(text, options) {
+            for glyph in line.glyphs {
+                let (pen, subpixel) = self.snap(x + glyph.position.x);
+                let ch = self.positioned_glyph(&glyph, subpixel);
+
+                self.draw_glyph(
+                    rd,
+                    &ch,
+                    pen,
+                    (y + glyph.position.y).round(),
+                    options.scale,
+                    color,
+                );
             }
+        }
+
+        self.upload(rd.gl);
+    }
 
-            let xpos = x + ch.bearing.x;
-            let ypos = y - (ch.height - ch.bearing.y);
+    /// Emit the quad for `ch` with its origin on the baseline at `[x, y]`.
+    pub fn draw_glyph(
+        &self,
+        rd: &mut Renderer,
+        ch: &Glyph,
+        x: f32,
+        y: f32,
+        scale: f32,
+        color: Vec4,
+    ) {
+        //Spaces, tabs and control characters.
+        if ch.width == 0.0 || ch.height == 0.0 {
+            return;
+        }
 
-            let w = ch.width;
-            let h = ch.height;
+        let xpos = x + ch.bearing.x * scale;
+        let ypos = y - (ch.height - ch.bearing.y) * scale;
+
+        let w = ch.width * scale;
+        let h = ch.height * scale;
+
+        //The projection matrix is top left which flips the y.
+        //So we no longer need to flip UV's.
+        //~~The y UV is flipped here. !uv.y~~
+        let UvRect {
+            u0: uv_left,
+            v0: uv_bottom,
+            u1: uv_right,
+            v1: uv_top,
+        } = ch.uv;
+
+        let (uv_left, uv_right) = if ch.color {
+            (uv_left + COLOR_UV_OFFSET, uv_right + COLOR_UV_OFFSET)
+        } else {
+            (uv_left, uv_right)
+        };
+
+        //Top left, Bottom left, Bottom right
+        //Bottom right, Top right, Top left
+        #[rustfmt::skip]
+        let vert = [
+            vertex!((xpos, ypos + h),     color, (uv_left, uv_bottom)),
+            vertex!((xpos, ypos),         color, (uv_left, uv_top)),
+            vertex!((xpos + w, ypos),     color, (uv_right, uv_top)),
+            vertex!((xpos + w, ypos),     color, (uv_right, uv_top)),
+            vertex!((xpos + w, ypos + h), color, (uv_right, uv_bottom)),
+            vertex!((xpos, ypos + h),     color, (uv_left, uv_bottom)),
+        ];
+
+        rd.vertices.extend(vert);
+    }
 
-            //The projection matrix is top left which flips the y.
-            //So we no longer need to flip UV's.
-            //~~The y UV is flipped here. !uv.y~~
-            let uv_left = ch.uv;
-            let uv_right = ch.uv + (ch.width / self.width as f32);
-            let uv_top = h / self.height as f32;
-            let uv_bottom = 0.0;
+    /// Solid rectangle with its bottom left corner at `[x, y]`, drawn from the atlas so it can share
+    /// a draw call with the text.
+    pub fn draw_rect(&self, rd: &mut Renderer, x: f32, y: f32, w: f32, h: f32, color: Vec4) {
+        let Some(solid) = self.bitmap.glyphs.get(&SOLID) else {
+            return;
+        };
+        let uv = Vec2::new(
+            (solid.uv.u0 + solid.uv.u1) / 2.0,
+            (solid.uv.v0 + solid.uv.v1) / 2.0,
+        );
 
-            //Top left, Bottom left, Bottom right
-            //Bottom right, Top right, Top left
-            #[rustfmt::skip]
-            let vert = [
-                vertex!((xpos, ypos + h),     color, (uv_left, uv_bottom)),
-                vertex!((xpos, ypos),         color, (uv_left, uv_top)),
-                vertex!((xpos + w, ypos),     color, (uv_right, uv_top)),
-                vertex!((xpos + w, ypos),     color, (uv_right, uv_top)),
-                vertex!((xpos + w, ypos + h), color, (uv_right, uv_bottom)),
-                vertex!((xpos, ypos + h),     color, (uv_left, uv_bottom)),
-            ];
+        #[rustfmt::skip]
+        let vert = [
+            vertex!((x, y + h),     color, uv),
+            vertex!((x, y),         color, uv),
+            vertex!((x + w, y),     color, uv),
+            vertex!((x + w, y),     color, uv),
+            vertex!((x + w, y + h), color, uv),
+            vertex!((x, y + h),     color, uv),
+        ];
+
+        rd.vertices.extend(vert);
+    }
 
-            rd.vertices.extend(vert);
+    /// Append `font` to the fallback chain. Characters are drawn with the first face that has them.
+    #[cfg(feature = "freetype")]
+    pub fn add_fallback(&mut self, lib: &Library, font: &'static [u8]) {
+        let mut face = FontFace::new(lib, font, self.size, self.mode);
+        face.set_variations(&self.variations);
+        self.faces.push(face);
+    }
 
-            // Advance cursors for the next glyph
-            x += (ch.advance.x) as f32;
-            //There are no characters with vertical advance without using the `VerticalLayout` flag.
-            y += (ch.advance.y) as f32;
+    /// Split a pen position into the whole pixel a glyph is drawn at and the closest of the
+    /// [`Atlas::subpixel`] offsets to rasterize it with.
+    pub fn snap(&self, x: f32) -> (f32, u8) {
+        let positions = self.subpixel.max(1) as f32;
+        let steps = (x * positions).round();
+        let pixel = (steps / positions).floor();
+        (pixel, (steps - pixel * positions) as u8)
+    }
+
+    /// The glyph to draw for a laid out glyph, missing characters and a full atlas draw [`Atlas::fallback`].
+    /// `subpixel` comes from [`Atlas::snap`].
+    pub fn positioned_glyph(&mut self, glyph: &PositionedGlyph, subpixel: u8) -> Glyph {
+        let key = if glyph.id == 0 {
+            self.fallback
+        } else {
+            GlyphKey {
+                face: glyph.face,
+                id: glyph.id,
+                subpixel,
+            }
+        };
+        match self.glyph(key) {
+            Some(ch) => ch,
+            None => self
+                .bitmap
+                .glyphs
+                .get(&self.fallback)
+                .copied()
+                .unwrap_or_default(),
         }
     }
-}
 
-pub unsafe fn load_font(rd: &Renderer, font: &[u8]) -> Atlas {
-    let gl = &rd.gl;
+    /// Returns the glyph for `key`, rasterizing it into the bitmap the first time it's seen.
+    /// `None` if the bitmap can't grow any further.
+    pub fn glyph(&mut self, key: GlyphKey) -> Option<Glyph> {
+        if let Some(glyph) = self.bitmap.glyphs.get(&key) {
+            return Some(*glyph);
+        }
+        if let Some(glyph) = self.color.as_ref().and_then(|color| color.glyphs.get(&key)) {
+            return Some(*glyph);
+        }
+        self.rasterize(key)
+    }
+
+    /// Shape `text` and load every glyph it uses, ligatures included. Call this before
+    /// [`Atlas::save`] so the baked atlas has everything it needs.
+    pub fn preload(&mut self, text: &str) {
+        for line in self.layout(text, TextOptions::default()) {
+            for glyph in &line.glyphs {
+                for subpixel in 0..self.subpixel.max(1) {
+                    self.positioned_glyph(glyph, subpixel);
+                }
+            }
+        }
+    }
 
-    let lib = Library::init().unwrap();
-    // FT_Library_SetLcdFilter(lib.raw(), FT_LCD_FILTER_DEFAULT);
-
-    let mut face = lib.new_memory_face2(font, 0).unwrap();
-    face.set_pixel_sizes(0, FONT_SIZE).unwrap();
-
-    let mut width = 0;
-    let mut height = 0;
-    #[allow(invalid_value)]
-    let mut glyphs: [Glyph; 128] = std::mem::zeroed();
-
-    //Load symbols, numbers and letters.
-    for i in 32..127 {
-        // face.load_char(i, LoadFlag::RENDER).unwrap();
-        let err = FT_Load_Char(
-            face.raw_mut(),
-            i as u32,
-            // FT_LOAD_RENDER | FT_RENDER_MODE_SDF as i32,
-            FT_LOAD_RENDER,
+    #[cfg(feature = "freetype")]
+    fn rasterize(&mut self, key: GlyphKey) -> Option<Glyph> {
+        let font = &self.faces[key.face];
+        let face = font.face.as_ref()?;
+        let shift = key.subpixel as f32 / self.subpixel.max(1) as f32;
+        let (glyph, buffer) = rasterize(
+            face,
+            key.id,
+            self.mode,
+            self.synthetic,
+            self.options,
+            font.bitmap_scale,
+            shift,
         );
-
-        if err != FT_Err_Ok {
-            panic!("{}", Error::from(err));
+        if glyph.color {
+            self.color
+                .get_or_insert_with(|| AtlasBitmap::new(Mode::Color))
+                .insert(key, glyph, &buffer)
+        } else {
+            self.bitmap.insert(key, glyph, &buffer)
         }
+    }
+
+    /// Without FreeType there's no way to draw glyphs that weren't baked.
+    #[cfg(not(feature = "freetype"))]
+    fn rasterize(&mut self, _key: GlyphKey) -> Option<Glyph> {
+        None
+    }
 
-        let glyph = face.glyph();
-        let bitmap = glyph.bitmap();
+    /// Copy the glyphs added since the last call into the texture, creating it the first time.
+    pub fn upload(&mut self, gl: &glow::Context) -> glow::NativeTexture {
+        unsafe {
+            let texture = upload_page(gl, &mut self.bitmap, &mut self.texture);
 
-        width += bitmap.width();
+            //Color glyphs are sampled from the second texture unit, see `text.frag`.
+            if let Some(color) = &mut self.color {
+                gl.active_texture(glow::TEXTURE1);
+                upload_page(gl, color, &mut self.color_texture);
+                gl.active_texture(glow::TEXTURE0);
+            }
 
-        if height < bitmap.rows() {
-            height = bitmap.rows();
+            texture
         }
+    }
+}
 
-        glyph.render_glyph(RenderMode::Normal).unwrap();
+/// Copy the rows of `bitmap` that changed into `texture`, creating it the first time.
+unsafe fn upload_page(
+    gl: &glow::Context,
+    bitmap: &mut AtlasBitmap,
+    texture: &mut Option<glow::NativeTexture>,
+) -> glow::NativeTexture {
+    let texture = *texture.get_or_insert_with(|| {
+        let max = gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE);
+        bitmap.max_height = bitmap.max_height.min(max);
+        create_texture(gl)
+    });
 
-        //Bitshift by 6 to get value in pixels. (2^6 = 64, advance is 1/64 pixels)
-        glyphs[i].advance = Vec2::new(
-            (glyph.advance().x >> 6) as f32,
-            (glyph.advance().y >> 6) as f32,
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+
+    let format = bitmap.mode.format();
+    if bitmap.resized {
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            format as i32,
+            bitmap.width,
+            bitmap.height,
+            0,
+            format,
+            glow::UNSIGNED_BYTE,
+            Some(&bitmap.pixels),
         );
-        glyphs[i].width = bitmap.width() as f32;
-        glyphs[i].height = bitmap.rows() as f32;
-        glyphs[i].bearing = Vec2::new(glyph.bitmap_left() as f32, glyph.bitmap_top() as f32);
-        glyphs[i].buffer = bitmap.buffer().to_vec();
-        assert_eq!(
-            glyphs[i].buffer.len() as f32,
-            glyphs[i].width * glyphs[i].height
+    } else if let Some(rows) = bitmap.dirty.clone() {
+        //Whole rows are contiguous in the bitmap, so there's no need for UNPACK_ROW_LENGTH.
+        let row = (bitmap.width * bitmap.mode.channels()) as usize;
+        let pixels = &bitmap.pixels[rows.start as usize * row..rows.end as usize * row];
+        gl.tex_sub_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            0,
+            rows.start,
+            bitmap.width,
+            rows.len() as i32,
+            format,
+            glow::UNSIGNED_BYTE,
+            glow::PixelUnpackData::Slice(pixels),
         );
     }
 
-    let texture = unsafe { gl.create_texture().unwrap() };
+    bitmap.resized = false;
+    bitmap.dirty = None;
+    check_error(gl);
+
+    texture
+}
+
+/// Rows of a FreeType bitmap from the top down. A negative pitch means they're stored bottom up.
+pub fn bitmap_rows(buffer: &[u8], pitch: i32) -> Vec<&[u8]> {
+    if pitch == 0 {
+        return Vec::new();
+    }
+    let mut rows: Vec<&[u8]> = buffer.chunks(pitch.unsigned_abs() as usize).collect();
+    if pitch < 0 {
+        rows.reverse();
+    }
+    rows
+}
+
+/// Returns the glyph metrics and its bitmap, rows are tightly packed with [`Mode::channels`] bytes per pixel.
+/// Outlines are moved `shift` pixels to the right first.
+#[cfg(feature = "freetype")]
+fn rasterize(
+    face: &Face<&'static [u8]>,
+    id: u32,
+    mode: Mode,
+    synthetic: Synthetic,
+    options: FontOptions,
+    bitmap_scale: f32,
+    shift: f32,
+) -> (Glyph, Vec<u8>) {
+    let (mut flags, render_mode) = options.load(mode);
+
+    //Only `text.frag` knows how to draw color glyphs.
+    if mode == Mode::Coverage && face.has_color() {
+        flags |= LoadFlag::COLOR;
+    }
+
+    face.load_glyph(id, flags).unwrap();
+
+    //These work on the outline in the glyph slot, so they have to happen before rendering.
+    unsafe {
+        if synthetic.bold {
+            freetype::ffi::FT_GlyphSlot_Embolden(face.raw().glyph);
+        }
+        if synthetic.oblique {
+            freetype::ffi::FT_GlyphSlot_Oblique(face.raw().glyph);
+        }
+
+        let slot = face.raw().glyph;
+        if (*slot).format == freetype::ffi::FT_GLYPH_FORMAT_OUTLINE {
+            if options.embolden > 0.0 {
+                let strength = (options.embolden * 64.0).round() as freetype::ffi::FT_Pos;
+                freetype::ffi::FT_Outline_Embolden(&mut (*slot).outline, strength);
+                //Wider glyphs need more room, the same as FT_GlyphSlot_Embolden.
+                if (*slot).advance.x != 0 {
+                    (*slot).advance.x += strength;
+                }
+            }
+            if shift != 0.0 {
+                let shift = (shift * 64.0).round() as freetype::ffi::FT_Pos;
+                freetype::ffi::FT_Outline_Translate(&(*slot).outline, shift, 0);
+            }
+        }
+    }
+
+    let glyph = face.glyph();
+    glyph.render_glyph(render_mode).unwrap();
+
+    let bitmap = glyph.bitmap();
+    if let Ok(PixelMode::Bgra) = bitmap.pixel_mode() {
+        return color_glyph(glyph, bitmap_scale);
+    }
+
+    let channels = mode.channels();
+    //LCD bitmaps are three times wider, one byte per subpixel.
+    let width = bitmap.width() / channels;
+    let stride = bitmap.width() as usize;
+    let mono = matches!(bitmap.pixel_mode(), Ok(PixelMode::Mono));
+
+    //Rows can be padded, so copy them one at a time.
+    let mut buffer = Vec::with_capacity(stride * bitmap.rows() as usize);
+    for row in bitmap_rows(bitmap.buffer(), bitmap.pitch()) {
+        if mono {
+            buffer.extend(unpack_mono(row, stride));
+        } else {
+            buffer.extend_from_slice(&row[..stride]);
+        }
+    }
+
+    if let Mode::Lcd {
+        order: PixelOrder::Bgr,
+        ..
+    } = mode
+    {
+        for pixel in buffer.chunks_exact_mut(3) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    let glyph = Glyph {
+        //26.6 fixed point, 1/64 of a pixel.
+        advance: Vec2::new(
+            glyph.advance().x as f32 / 64.0,
+            glyph.advance().y as f32 / 64.0,
+        ),
+        width: width as f32,
+        height: bitmap.rows() as f32,
+        bearing: Vec2::new(glyph.bitmap_left() as f32, glyph.bitmap_top() as f32),
+        ..Default::default()
+    };
+    assert_eq!(
+        buffer.len() as f32,
+        glyph.width * glyph.height * channels as f32
+    );
+
+    if mode == Mode::Sdf {
+        return pad_sdf(glyph, &buffer);
+    }
+    (glyph, buffer)
+}
+
+/// Rasterize `font` at `size` pixels per em.
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_font(rd: &Renderer, font: &'static [u8], size: u32) -> Atlas {
+    let lib = Library::init().unwrap();
+    load_face(rd, &lib, font, size, Mode::Coverage)
+}
+
+/// Rasterize `font` as a signed distance field, see [`Mode::Sdf`].
+/// A single atlas can be drawn at any [`TextOptions::scale`].
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_font_sdf(rd: &Renderer, font: &'static [u8], size: u32) -> Atlas {
+    let lib = Library::init().unwrap();
+    load_face(rd, &lib, font, size, Mode::Sdf)
+}
+
+/// Rasterize `font` with subpixel antialiasing, see [`Mode::Lcd`].
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_font_lcd(
+    rd: &Renderer,
+    font: &'static [u8],
+    size: u32,
+    order: PixelOrder,
+    filter: LcdFilter,
+) -> Atlas {
+    let lib = Library::init().unwrap();
+    load_face(rd, &lib, font, size, Mode::Lcd { order, filter })
+}
+
+/// Same as [`load_font`] but shares an existing FreeType library.
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_face(
+    rd: &Renderer,
+    lib: &Library,
+    font: &'static [u8],
+    size: u32,
+    mode: Mode,
+) -> Atlas {
+    let mut atlas = Atlas::new(lib, font, size, mode);
+    atlas.upload(rd.gl);
+    atlas
+}
+
+#[cfg(feature = "freetype")]
+impl Atlas {
+    /// Rasterize `font` at `size` pixels per em without touching the GPU, see [`Atlas::upload`].
+    pub fn new(lib: &Library, font: &'static [u8], size: u32, mode: Mode) -> Self {
+        Self::new_with_variations(lib, font, size, mode, &[])
+    }
+
+    /// Same as [`Atlas::new`] with the design axes of a variable font at `variations`.
+    pub fn new_with_variations(
+        lib: &Library,
+        font: &'static [u8],
+        size: u32,
+        mode: Mode,
+        variations: &[Variation],
+    ) -> Self {
+        let mut atlas = Self::empty(lib, font, size, mode, variations);
+        atlas.insert_solid();
+        atlas.insert_fallback();
+        atlas.preload_ascii();
+        atlas
+    }
+
+    /// An atlas without any glyphs, so fields that change how glyphs are rasterized can be set
+    /// before anything is rasterized. Call [`Atlas::reload`] once they're set.
+    pub(crate) fn empty(
+        lib: &Library,
+        font: &'static [u8],
+        size: u32,
+        mode: Mode,
+        variations: &[Variation],
+    ) -> Self {
+        let mut primary = FontFace::new(lib, font, size, mode);
+        primary.set_variations(variations);
+        //Emoji and icon fonts often don't have a '?', draw their `.notdef` instead.
+        let fallback = GlyphKey {
+            face: 0,
+            id: primary
+                .shaper
+                .face
+                .glyph_index('?')
+                .map_or(0, |id| id.0 as u32),
+            subpixel: 0,
+        };
+
+        let mut atlas = Atlas {
+            size,
+            mode,
+            line_height: 0.0,
+            ascender: 0.0,
+            descender: 0.0,
+            line_gap: 0.0,
+            units_per_em: 0,
+            bitmap: AtlasBitmap::new(mode),
+            texture: None,
+            color: None,
+            color_texture: None,
+            faces: vec![primary],
+            fallback,
+            variations: variations.to_vec(),
+            synthetic: Synthetic::default(),
+            subpixel: 1,
+            options: FontOptions::default(),
+        };
+
+        atlas.set_metrics();
+        atlas
+    }
+
+    /// Rasterize each glyph at `positions` evenly spaced horizontal offsets and draw the one closest
+    /// to the pen, so small text keeps its spacing even instead of snapping every glyph to a whole
+    /// pixel. Costs up to `positions` times the atlas space, 3 or 4 is plenty.
+    pub fn set_subpixel(&mut self, positions: u8) {
+        self.subpixel = positions.max(1);
+        self.reload();
+    }
+
+    /// FreeType's size metrics leave out the line gap, so read the hhea/OS/2 values instead.
+    /// They change with the weight of variable fonts.
+    pub(crate) fn set_metrics(&mut self) {
+        let shaper = &self.faces[0].shaper;
+        let px = |units: i16| (units as f32 * shaper.scale).round();
+        self.ascender = px(shaper.face.ascender());
+        self.descender = px(shaper.face.descender());
+        self.line_gap = px(shaper.face.line_gap());
+        self.units_per_em = shaper.face.units_per_em() as u16;
+        self.line_height = self.ascender - self.descender + self.line_gap;
+    }
+
+    /// Throw away every glyph, they're rasterized again as they're drawn.
+    pub(crate) fn reload(&mut self) {
+        self.bitmap.clear();
+        if let Some(color) = &mut self.color {
+            color.clear();
+        }
+        self.insert_solid();
+        self.insert_fallback();
+        self.preload_ascii();
+    }
+
+    /// 3x3 so linear filtering at the center texel never reaches the empty padding.
+    /// It's the first thing packed so it sits in the top left corner, where the (0, 0) UV
+    /// of [`Renderer::quad`] samples it.
+    pub(crate) fn insert_solid(&mut self) {
+        let solid = Glyph {
+            width: 3.0,
+            height: 3.0,
+            ..Default::default()
+        };
+        let buffer = vec![255; (9 * self.mode.channels()) as usize];
+        self.bitmap.insert(SOLID, solid, &buffer);
+    }
+
+    /// `.notdef` is outside the ASCII range, rasterize it up front so it's always there to draw
+    /// and gets baked.
+    pub(crate) fn insert_fallback(&mut self) {
+        self.glyph(self.fallback);
+    }
+
+    /// Load symbols, numbers and letters up front, everything else is loaded on demand.
+    pub(crate) fn preload_ascii(&mut self) {
+        for c in ' '..='~' {
+            if let Some(id) = self.faces[0].shaper.face.glyph_index(c) {
+                let id = id.0 as u32;
+                self.glyph(GlyphKey {
+                    face: 0,
+                    id,
+                    subpixel: 0,
+                });
+            }
+        }
+    }
+}
+
+/// Create an empty texture for an atlas, [`Atlas::upload`] fills it in.
+unsafe fn create_texture(gl: &glow::Context) -> glow::NativeTexture {
+    let texture = gl.create_texture().unwrap();
     gl.bind_texture(glow::TEXTURE_2D, Some(texture));
 
     gl.tex_parameter_i32(
@@ -164,63 +886,6 @@ pub unsafe fn load_font(rd: &Renderer, font: &[u8]) -> Atlas {
         glow::TEXTURE_WRAP_T,
         glow::CLAMP_TO_EDGE as i32,
     );
-    gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
-
-    debug_assert!(width >= 0);
-    debug_assert!(width < glow::MAX_TEXTURE_SIZE as i32);
-    debug_assert!(height < glow::MAX_TEXTURE_SIZE as i32);
-
-    //If we don't zero this texture, bad things will happen.
-    gl.tex_image_2d(
-        glow::TEXTURE_2D,
-        0,
-        glow::RED as i32,
-        width as i32,
-        height as i32,
-        0,
-        glow::RED,
-        glow::UNSIGNED_BYTE,
-        // Some(&vec![0; (width * height) as usize]),
-        None,
-    );
-
-    let mut x = 0;
-
-    for i in 32..127 {
-        glyphs[i].uv = x as f32 / width as f32;
-
-        if x + glyphs[i].width as i32 > glow::TEXTURE_WIDTH as i32
-            || 0 + glyphs[i].height as i32 > glow::TEXTURE_HEIGHT as i32
-        {
-            panic!("texture is too big!");
-        }
-
-        if (glyphs[i].width as i32) < 0 || (glyphs[i].height as i32) < 0 {
-            panic!("too small!");
-        }
 
-        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
-        gl.tex_sub_image_2d(
-            glow::TEXTURE_2D,
-            0,
-            x,
-            0,
-            glyphs[i].width as i32,
-            glyphs[i].height as i32,
-            glow::RED,
-            glow::UNSIGNED_BYTE,
-            glow::PixelUnpackData::Slice(&glyphs[i].buffer),
-        );
-
-        check_error(gl);
-
-        x += glyphs[i].width as i32;
-    }
-
-    Atlas {
-        width,
-        height,
-        texture,
-        glyphs,
-    }
+    texture
 }
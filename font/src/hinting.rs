@@ -0,0 +1,116 @@
+#[cfg(feature = "freetype")]
+use crate::*;
+#[cfg(feature = "freetype")]
+use freetype::{face::LoadFlag, RenderMode};
+
+/// How far outlines are moved to line up with the pixel grid before they're rasterized.
+///https://freetype.org/freetype2/docs/reference/ft2-base_interface.html#ft_load_target_xxx
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Hinting {
+    /// The outline as designed, blurrier at small sizes but true to the font. Best on high-DPI screens.
+    None,
+    /// Vertical only, keeps the shapes and spacing of the glyphs.
+    Light,
+    /// The font's own hinting instructions, or the autohinter when it doesn't have any.
+    #[default]
+    Normal,
+    /// FreeType's autohinter even when the font has instructions.
+    Autohint,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Antialias {
+    /// Every pixel is either fully covered or empty, for pixel art UIs.
+    /// Only applies to [`Mode::Coverage`] atlases.
+    Mono,
+    #[default]
+    Gray,
+}
+
+/// How glyphs are loaded and rasterized, see [`Atlas::set_options`].
+///
+/// ```rs
+/// let pixel_art = FontOptions {
+///     hinting: Hinting::Normal,
+///     antialias: Antialias::Mono,
+///     ..Default::default()
+/// };
+/// let mut atlas = load_font_options(&rd, font, 16, pixel_art);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FontOptions {
+    pub hinting: Hinting,
+    pub antialias: Antialias,
+    /// Pixels the outline grows by, about half on each side. Advances grow by the same amount
+    /// so proportional text doesn't overlap. 0.0 leaves it alone.
+    ///https://freetype.org/freetype2/docs/reference/ft2-outline_processing.html#ft_outline_embolden
+    pub embolden: f32,
+}
+
+impl FontOptions {
+    /// FreeType load flags and render mode for `mode`.
+    #[cfg(feature = "freetype")]
+    pub(crate) fn load(&self, mode: Mode) -> (LoadFlag, RenderMode) {
+        let mono = self.antialias == Antialias::Mono && mode == Mode::Coverage;
+
+        //The target picks the hinting algorithm, so only one of them can be set.
+        let target = match (mode, self.hinting) {
+            (_, Hinting::Light) => LoadFlag::TARGET_LIGHT,
+            (Mode::Lcd { .. }, _) => LoadFlag::TARGET_LCD,
+            _ if mono => LoadFlag::TARGET_MONO,
+            _ => LoadFlag::TARGET_NORMAL,
+        };
+        let flags = match self.hinting {
+            Hinting::None => LoadFlag::NO_HINTING,
+            Hinting::Light | Hinting::Normal => target,
+            Hinting::Autohint => target | LoadFlag::FORCE_AUTOHINT,
+        };
+
+        let render = match mode {
+            Mode::Sdf => RenderMode::Sdf,
+            Mode::Lcd { .. } => RenderMode::Lcd,
+            _ if mono => RenderMode::Mono,
+            _ => RenderMode::Normal,
+        };
+        (flags, render)
+    }
+}
+
+/// Spread the bits of a 1 bit per pixel row to a byte per pixel.
+pub fn unpack_mono(row: &[u8], width: usize) -> impl Iterator<Item = u8> + '_ {
+    (0..width).map(|x| {
+        if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+            255
+        } else {
+            0
+        }
+    })
+}
+
+#[cfg(feature = "freetype")]
+impl Atlas {
+    /// Rasterize every glyph again with `options`.
+    pub fn set_options(&mut self, options: FontOptions) {
+        self.options = options;
+        self.reload();
+    }
+}
+
+/// Rasterize `font` at `size` pixels per em with `options`.
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_font_options(
+    rd: &Renderer,
+    font: &'static [u8],
+    size: u32,
+    options: FontOptions,
+) -> Atlas {
+    let lib = freetype::Library::init().unwrap();
+    let mut atlas = Atlas::empty(&lib, font, size, Mode::Coverage, &[]);
+    atlas.options = options;
+    atlas.reload();
+    atlas.upload(rd.gl);
+    atlas
+}
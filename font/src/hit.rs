@@ -0,0 +1,217 @@
+use crate::*;
+use std::ops::Range;
+use unicode_bidi::{bidi_class, BidiClass};
+
+/// What's under a point, see [`Atlas::hit_test`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hit {
+    /// Index into the lines from [`Atlas::layout`].
+    pub line: usize,
+    /// Byte index of the closest caret position, the edge of the character nearest the point.
+    pub index: usize,
+    /// Byte index of the character the point is over, `None` before or after the line.
+    pub character: Option<usize>,
+}
+
+/// Caret rectangle for a byte index, relative to the first baseline like the glyphs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Caret {
+    pub line: usize,
+    pub x: f32,
+    /// Bottom edge, the descender below the baseline.
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// One character and its nonspacing marks on a line in visual order. Ligatures are split evenly between the characters
+/// they replace so the caret can stop inside them.
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    start: usize,
+    end: usize,
+    left: f32,
+    right: f32,
+    rtl: bool,
+}
+
+impl Cell {
+    /// Where the caret goes before the character, after it in right to left text.
+    fn leading(&self) -> f32 {
+        if self.rtl {
+            self.right
+        } else {
+            self.left
+        }
+    }
+
+    fn trailing(&self) -> f32 {
+        if self.rtl {
+            self.left
+        } else {
+            self.right
+        }
+    }
+}
+
+/// Nonspacing marks (bidi class NSM) like the accent in "e\u{301}" belong to the character
+/// before them. This isn't full grapheme segmentation, spacing marks, emoji ZWJ sequences,
+/// skin tone modifiers, flags and Hangul jamo are still split into separate characters.
+pub(crate) fn is_mark(c: char) -> bool {
+    bidi_class(c) == BidiClass::NSM
+}
+
+fn cells(text: &str, line: &Line) -> Vec<Cell> {
+    //Each cluster runs up to the next one in logical order.
+    let mut clusters: Vec<usize> = line.glyphs.iter().map(|g| g.cluster).collect();
+    clusters.sort_unstable();
+    clusters.dedup();
+    let end_of = |cluster: usize| {
+        let next = clusters.partition_point(|&c| c <= cluster);
+        clusters.get(next).copied().unwrap_or(line.range.end)
+    };
+
+    let mut cells = Vec::new();
+    let mut glyphs = line.glyphs.iter().peekable();
+    while let Some(first) = glyphs.next() {
+        //Marks share a cluster with their base.
+        let mut width = first.advance.x;
+        while let Some(mark) = glyphs.next_if(|g| g.cluster == first.cluster) {
+            width += mark.advance.x;
+        }
+
+        let (start, end) = (first.cluster, end_of(first.cluster).max(first.cluster));
+        let rtl = first.level % 2 == 1;
+        //Byte ranges of the characters with their nonspacing marks, the caret can't go between them.
+        let mut chars: Vec<Range<usize>> = Vec::new();
+        for (offset, c) in text[start..end].char_indices() {
+            let range = offset..offset + c.len_utf8();
+            match chars.last_mut() {
+                Some(last) if is_mark(c) => last.end = range.end,
+                _ => chars.push(range),
+            }
+        }
+        let count = chars.len().max(1);
+        let step = width / count as f32;
+
+        //The glyph position includes the shaper offset, which is zero for anything but marks.
+        let left = first.position.x;
+        for (i, range) in chars.into_iter().enumerate() {
+            let slot = if rtl { count - 1 - i } else { i };
+            cells.push(Cell {
+                start: start + range.start,
+                end: start + range.end,
+                left: left + step * slot as f32,
+                right: left + step * (slot + 1) as f32,
+                rtl,
+            });
+        }
+    }
+
+    cells.sort_by(|a, b| a.left.total_cmp(&b.left));
+    cells
+}
+
+/// Caret position and character under `x` on a single line. Works with any laid out line,
+/// including the ones in [`RichText`].
+pub fn hit_line(text: &str, line: &Line, x: f32) -> (usize, Option<usize>) {
+    let cells = cells(text, line);
+    let (Some(first), Some(last)) = (cells.first(), cells.last()) else {
+        return (line.range.start, None);
+    };
+
+    if x < first.left {
+        return (edge_index(first, true), None);
+    }
+
+    match cells.iter().find(|cell| x < cell.right) {
+        Some(cell) => {
+            let before = x < (cell.left + cell.right) / 2.0;
+            (edge_index(cell, before), Some(cell.start))
+        }
+        None => (edge_index(last, false), None),
+    }
+}
+
+/// Byte index of the caret at the left or right edge of `cell`.
+fn edge_index(cell: &Cell, left: bool) -> usize {
+    if left != cell.rtl {
+        cell.start
+    } else {
+        cell.end
+    }
+}
+
+/// Horizontal caret position for byte `index` on a single line.
+pub fn caret_x(text: &str, line: &Line, index: usize) -> f32 {
+    let cells = cells(text, line);
+    if let Some(cell) = cells.iter().find(|c| c.start <= index && index < c.end) {
+        return cell.leading();
+    }
+    if let Some(cell) = cells.iter().find(|c| c.end == index) {
+        return cell.trailing();
+    }
+    line.x
+}
+
+/// Horizontal spans covering the characters of `range` on a single line, left to right.
+/// Mixed direction text can split one selection into several spans.
+pub fn selection_spans(text: &str, line: &Line, range: Range<usize>) -> Vec<(f32, f32)> {
+    let mut spans: Vec<(f32, f32)> = Vec::new();
+    for cell in cells(text, line)
+        .iter()
+        .filter(|cell| range.start <= cell.start && cell.end <= range.end)
+    {
+        match spans.last_mut() {
+            Some(last) if (last.1 - cell.left).abs() < 0.5 => last.1 = cell.right,
+            _ => spans.push((cell.left, cell.right)),
+        }
+    }
+    spans
+}
+
+/// The line `index` is on. An index where a wrapped line ends goes to the start of the next one.
+pub fn line_at(lines: &[Line], index: usize) -> usize {
+    lines
+        .iter()
+        .rposition(|line| line.range.start <= index)
+        .unwrap_or(0)
+}
+
+impl Atlas {
+    /// Find what's under `point`, relative to the `[x, y]` the text was drawn at.
+    /// Points above or below the text go to the first or last line.
+    pub fn hit_test(&self, text: &str, point: Vec2, options: TextOptions) -> Hit {
+        let lines = self.layout(text, options);
+        let ascender = self.ascender * options.scale;
+        let advance = self.line_advance(options);
+
+        //Each line owns the space from its ascender down to the next line's ascender.
+        let line = lines
+            .iter()
+            .position(|line| point.y >= line.y + ascender - advance)
+            .unwrap_or(lines.len() - 1);
+
+        let (index, character) = hit_line(text, &lines[line], point.x);
+        Hit {
+            line,
+            index,
+            character,
+        }
+    }
+
+    /// The caret for byte `index`, one pixel wide and from the descender to the ascender.
+    pub fn caret(&self, text: &str, index: usize, options: TextOptions) -> Caret {
+        let lines = self.layout(text, options);
+        let line = line_at(&lines, index);
+        let index = index.min(lines[line].range.end);
+
+        Caret {
+            line,
+            x: caret_x(text, &lines[line], index),
+            y: lines[line].y + (self.descender * options.scale).round(),
+            width: 1.0,
+            height: ((self.ascender - self.descender) * options.scale).round(),
+        }
+    }
+}
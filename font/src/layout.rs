@@ -0,0 +1,336 @@
+use crate::*;
+use std::ops::Range;
+
+/// A shaped glyph with its position relative to the first baseline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PositionedGlyph {
+    /// Index into [`Atlas::faces`].
+    pub face: usize,
+    pub id: u32,
+    /// Byte offset into the whole text, see [`ShapedGlyph::cluster`].
+    pub cluster: usize,
+    /// Where the glyph's origin goes, shaper offsets included.
+    pub position: Vec2,
+    pub advance: Vec2,
+    /// See [`ShapedGlyph::level`].
+    pub level: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Line {
+    /// In visual order, left to right.
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Byte range of the line in the text, without the line break.
+    pub range: Range<usize>,
+    /// Left edge of the line after alignment.
+    pub x: f32,
+    /// Baseline relative to the first line, lines go downwards so this is <= 0.
+    pub y: f32,
+    /// Sum of the advances, not counting trailing whitespace.
+    pub width: f32,
+    /// The line was broken to fit `max_width` rather than ending at a '\n'.
+    pub wrapped: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TabWidth {
+    /// Multiple of the width of the primary face's space.
+    Spaces(u32),
+    Pixels(f32),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Stretch the spaces so wrapped lines fill the whole width.
+    /// The last line of a paragraph is left aligned.
+    Justify,
+}
+
+/// Logical size of a block of text, built from the same layout as [`Atlas::draw_text`].
+/// This is the box the pen moves through, glyphs like 'j' or 'f' can ink slightly outside of it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextMetrics {
+    /// Width of the widest line.
+    pub width: f32,
+    pub height: f32,
+    /// Width of each line.
+    pub lines: Vec<f32>,
+    /// Distance from the top of the box down to the first baseline.
+    /// The `y` passed to [`Atlas::draw_text`] is `baseline` below the top edge.
+    pub baseline: f32,
+}
+
+impl TextMetrics {
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+/// Whitespace we're allowed to break a line at.
+fn is_break(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{A0}' | '\u{2007}' | '\u{202F}')
+}
+
+fn is_break_at(text: &str, byte: usize) -> bool {
+    text[byte..].chars().next().is_some_and(is_break)
+}
+
+impl Atlas {
+    /// Shape and position `text`. Lines end at '\n' or, when [`TextOptions::max_width`] is set,
+    /// at the last space that fits. Words longer than the whole width are broken between glyphs.
+    pub fn layout(&self, text: &str, options: TextOptions) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let line_height = self.line_advance(options);
+
+        let tab_width = self.tab_width(options);
+
+        for paragraph in text.split('\n') {
+            let len = paragraph.len();
+            //"\r\n" ends a line the same as '\n'.
+            let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+
+            let mut glyphs = self.shape(paragraph, options);
+            for glyph in &mut glyphs {
+                glyph.advance = glyph.advance * options.scale;
+                glyph.offset = glyph.offset * options.scale;
+            }
+            wrap(
+                &mut lines,
+                line_height,
+                paragraph,
+                start,
+                &mut glyphs,
+                options.max_width,
+                |_| tab_width,
+            );
+            start += len + '\n'.len_utf8();
+        }
+
+        align(text, &mut lines, options);
+        lines
+    }
+
+    /// Distance between baselines with [`TextOptions::line_spacing`] applied, kept on whole pixels.
+    pub fn line_advance(&self, options: TextOptions) -> f32 {
+        (self.line_height * options.line_spacing * options.scale).round()
+    }
+
+    /// Distance between tab stops in pixels.
+    pub fn tab_width(&self, options: TextOptions) -> f32 {
+        match options.tab_width {
+            TabWidth::Spaces(spaces) => {
+                let space = self.faces[0]
+                    .shaper
+                    .shape(" ", options)
+                    .first()
+                    .map_or(0.0, |glyph| glyph.advance.x);
+                spaces as f32 * space * options.scale
+            }
+            TabWidth::Pixels(pixels) => pixels,
+        }
+    }
+
+    pub fn measure(&self, text: &str) -> TextMetrics {
+        self.measure_with(text, TextOptions::default())
+    }
+
+    pub fn measure_with(&self, text: &str, options: TextOptions) -> TextMetrics {
+        let lines: Vec<f32> = self
+            .layout(text, options)
+            .iter()
+            .map(|line| line.width)
+            .collect();
+
+        TextMetrics {
+            width: lines.iter().copied().fold(0.0, f32::max),
+            height: lines.len() as f32 * self.line_advance(options),
+            lines,
+            baseline: self.ascender * options.scale,
+        }
+    }
+}
+
+/// Stretch each tab so the next glyph starts on a tab stop, measured from the first glyph. `tab_width` gives the distance
+/// between stops for the glyph at a byte offset in `paragraph`.
+pub(crate) fn expand_tabs(
+    paragraph: &str,
+    glyphs: &mut [ShapedGlyph],
+    tab_width: impl Fn(usize) -> f32,
+) {
+    let mut x = 0.0;
+    for glyph in glyphs {
+        let tab_width = tab_width(glyph.cluster);
+        if paragraph[glyph.cluster..].starts_with('\t') && tab_width > 0.0 {
+            let stop = ((x / tab_width).floor() + 1.0) * tab_width;
+            glyph.advance.x = stop - x;
+        }
+        x += glyph.advance.x;
+    }
+}
+
+/// Break one paragraph into lines, `start` is the byte offset of the paragraph in the whole text.
+/// Tabs are expanded here since their stops are measured from the start of each line.
+pub(crate) fn wrap(
+    lines: &mut Vec<Line>,
+    line_height: f32,
+    paragraph: &str,
+    start: usize,
+    glyphs: &mut [ShapedGlyph],
+    max_width: Option<f32>,
+    tab_width: impl Fn(usize) -> f32,
+) {
+    let mut line_start = 0;
+    let mut x = 0.0;
+    //Index of the first glyph after the last space.
+    let mut last_break = None;
+
+    expand_tabs(paragraph, glyphs, &tab_width);
+
+    for i in 0..glyphs.len() {
+        let glyph = &glyphs[i];
+        let space = is_break_at(paragraph, glyph.cluster);
+
+        if let Some(max_width) = max_width {
+            if !space && i > line_start && x + glyph.advance.x > max_width {
+                let end = match last_break {
+                    Some(end) if end > line_start => end,
+                    _ => i,
+                };
+                let end_byte = glyphs[end].cluster;
+                push_line(
+                    lines,
+                    line_height,
+                    paragraph,
+                    start,
+                    &glyphs[line_start..end],
+                    end_byte,
+                    true,
+                );
+
+                //The new line starts a new row of tab stops.
+                expand_tabs(paragraph, &mut glyphs[end..], &tab_width);
+                x = glyphs[end..i].iter().map(|g| g.advance.x).sum();
+                line_start = end;
+                last_break = None;
+            }
+        }
+
+        if space {
+            last_break = Some(i + 1);
+        }
+        x += glyphs[i].advance.x;
+    }
+
+    push_line(
+        lines,
+        line_height,
+        paragraph,
+        start,
+        &glyphs[line_start..],
+        paragraph.len(),
+        false,
+    );
+}
+
+/// Move each line into place inside `max_width`, or the widest line when there's no limit.
+pub(crate) fn align(text: &str, lines: &mut [Line], options: TextOptions) {
+    let width = options
+        .max_width
+        .unwrap_or_else(|| lines.iter().map(|line| line.width).fold(0.0, f32::max));
+
+    for line in lines {
+        let extra = (width - line.width).max(0.0);
+
+        let shift = match options.align {
+            Align::Left | Align::Justify => 0.0,
+            Align::Center => (extra / 2.0).round(),
+            Align::Right => extra,
+        };
+
+        if options.align == Align::Justify && line.wrapped {
+            let Some(last) = line
+                .glyphs
+                .iter()
+                .rposition(|g| !is_break_at(text, g.cluster))
+            else {
+                continue;
+            };
+
+            let gaps = line.glyphs[..last]
+                .iter()
+                .filter(|g| is_break_at(text, g.cluster))
+                .count();
+
+            if gaps > 0 {
+                let gap = extra / gaps as f32;
+                let mut offset: f32 = 0.0;
+                for glyph in &mut line.glyphs[..=last] {
+                    glyph.position.x += offset.round();
+                    if is_break_at(text, glyph.cluster) {
+                        offset += gap;
+                    }
+                }
+                line.width = width;
+            }
+        }
+
+        line.x = shift;
+        for glyph in &mut line.glyphs {
+            glyph.position.x += shift;
+        }
+    }
+}
+
+/// `start` is the byte offset of the paragraph in `text`, `end` is where the line stops in the paragraph.
+fn push_line(
+    lines: &mut Vec<Line>,
+    line_height: f32,
+    paragraph: &str,
+    start: usize,
+    glyphs: &[ShapedGlyph],
+    end: usize,
+    wrapped: bool,
+) {
+    let y = -(lines.len() as f32) * line_height;
+    let mut x = 0.0;
+    let mut positioned = Vec::with_capacity(glyphs.len());
+
+    //Trailing spaces hang off the end of the line, so they're left out of the width and the reordering.
+    let visible = glyphs
+        .iter()
+        .rposition(|g| !is_break_at(paragraph, g.cluster))
+        .map_or(0, |i| i + 1);
+    let width = glyphs[..visible].iter().map(|g| g.advance.x).sum();
+
+    let levels: Vec<u8> = glyphs[..visible].iter().map(|g| g.level).collect();
+    for i in visual_order(&levels)
+        .into_iter()
+        .chain(visible..glyphs.len())
+    {
+        let shaped = &glyphs[i];
+        positioned.push(PositionedGlyph {
+            face: shaped.face,
+            id: shaped.id,
+            cluster: start + shaped.cluster,
+            position: Vec2::new(x + shaped.offset.x, y + shaped.offset.y),
+            advance: shaped.advance,
+            level: shaped.level,
+        });
+        x += shaped.advance.x;
+    }
+
+    let begin = glyphs.first().map(|g| g.cluster).unwrap_or(end);
+    lines.push(Line {
+        glyphs: positioned,
+        range: start + begin..start + end,
+        x: 0.0,
+        y,
+        width,
+        wrapped,
+    });
+}
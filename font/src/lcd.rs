@@ -0,0 +1,44 @@
+use crate::*;
+
+/// Order of the subpixels on the monitor, left to right.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PixelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+}
+
+/// Filter FreeType runs over the subpixels to reduce color fringes.
+///https://freetype.org/freetype2/docs/reference/ft2-lcd_rendering.html#ft_lcdfilter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LcdFilter {
+    None,
+    #[default]
+    Default,
+    Light,
+    Legacy,
+}
+
+#[cfg(feature = "freetype")]
+impl From<LcdFilter> for freetype::LcdFilter {
+    fn from(filter: LcdFilter) -> Self {
+        match filter {
+            LcdFilter::None => freetype::LcdFilter::LcdFilterNone,
+            LcdFilter::Default => freetype::LcdFilter::LcdFilterDefault,
+            LcdFilter::Light => freetype::LcdFilter::LcdFilterLight,
+            LcdFilter::Legacy => freetype::LcdFilter::LcdFilterLegacy,
+        }
+    }
+}
+
+/// Shader for [`Mode::Lcd`] atlases. Blends each color channel separately,
+/// so it needs [`Renderer::enable_lcd_blend`] instead of [`Renderer::enable_blend`].
+pub fn lcd_shader() -> glow::NativeProgram {
+    shader! {
+        include_str!("../shaders/simple.vert"),
+        include_str!("../shaders/lcd.frag"),
+        Vec2 => 0,
+        Vec2 => 1,
+        Vec4 => 2
+    }
+}
@@ -1,14 +1,49 @@
 #![feature(const_maybe_uninit_zeroed)]
 use glow::*;
 use std::mem::MaybeUninit;
+use std::ops::Range;
 
 extern crate nalgebra_glm as glm;
 
+pub mod bake;
+pub mod bitmap;
+pub mod color;
+pub mod database;
+#[cfg(feature = "freetype")]
+pub mod face;
+pub mod family;
+pub mod field;
 pub mod glyph;
+pub mod hinting;
+pub mod hit;
+pub mod layout;
+pub mod lcd;
 pub mod math;
-
+pub mod outline;
+pub mod rich;
+pub mod sdf;
+pub mod shape;
+pub mod variation;
+
+pub use bake::*;
+pub use bitmap::*;
+pub use color::*;
+pub use database::*;
+#[cfg(feature = "freetype")]
+pub use face::*;
+pub use family::*;
+pub use field::*;
 pub use glyph::*;
+pub use hinting::*;
+pub use hit::*;
+pub use layout::*;
+pub use lcd::*;
 pub use math::*;
+pub use outline::*;
+pub use rich::*;
+pub use sdf::*;
+pub use shape::*;
+pub use variation::*;
 
 #[cfg(test)]
 mod tests;
@@ -259,6 +294,15 @@ pub fn hex(hex: u32) -> Vec4 {
     )
 }
 
+/// A run of [`Renderer::vertices`] drawn with `texture` bound.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub texture: NativeTexture,
+    /// Color page of the atlas, bound to the second texture unit.
+    pub color: Option<NativeTexture>,
+    pub range: Range<usize>,
+}
+
 pub struct Renderer {
     pub gl: &'static glow::Context,
     pub vertices: Vec<Vertex>,
@@ -344,6 +388,10 @@ impl Renderer {
             let location = gl.get_uniform_location(basic, "projection").unwrap();
             gl.uniform_matrix_4_f32_slice(Some(&location), false, projection.as_slice());
 
+            //Color glyphs live in a second texture, see `COLOR_UV_OFFSET`.
+            let color_image = gl.get_uniform_location(basic, "color_image");
+            gl.uniform_1_i32(color_image.as_ref(), 1);
+
             Self {
                 gl,
                 vao,
@@ -458,21 +506,7 @@ impl Renderer {
 
     pub fn draw(&mut self) {
         unsafe {
-            //When replacing the entire data store, consider using glBufferSubData rather than completely recreating the data store with glBufferData. This avoids the cost of reallocating the data store.
-            if self.buffer_size != self.vertices.len() {
-                self.gl.buffer_data_u8_slice(
-                    glow::ARRAY_BUFFER,
-                    self.vertices.align_to::<u8>().1,
-                    glow::DYNAMIC_DRAW,
-                );
-                self.buffer_size = self.vertices.len();
-            } else {
-                self.gl.buffer_sub_data_u8_slice(
-                    glow::ARRAY_BUFFER,
-                    0,
-                    self.vertices.align_to::<u8>().1,
-                );
-            }
+            self.upload();
 
             // self.gl.draw_arrays(glow::LINES, 0, 2);
             self.gl
@@ -480,6 +514,45 @@ impl Renderer {
         }
     }
 
+    /// Draw each batch with its own texture, for text that spans more than one atlas.
+    pub fn draw_batches(&mut self, batches: &[Batch]) {
+        unsafe {
+            self.upload();
+
+            for batch in batches {
+                if let Some(color) = batch.color {
+                    self.gl.active_texture(glow::TEXTURE1);
+                    self.gl.bind_texture(glow::TEXTURE_2D, Some(color));
+                    self.gl.active_texture(glow::TEXTURE0);
+                }
+                self.gl.bind_texture(glow::TEXTURE_2D, Some(batch.texture));
+                self.gl.draw_arrays(
+                    glow::TRIANGLES,
+                    batch.range.start as i32,
+                    batch.range.len() as i32,
+                );
+            }
+        }
+    }
+
+    unsafe fn upload(&mut self) {
+        //When replacing the entire data store, consider using glBufferSubData rather than completely recreating the data store with glBufferData. This avoids the cost of reallocating the data store.
+        if self.buffer_size != self.vertices.len() {
+            self.gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                self.vertices.align_to::<u8>().1,
+                glow::DYNAMIC_DRAW,
+            );
+            self.buffer_size = self.vertices.len();
+        } else {
+            self.gl.buffer_sub_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                0,
+                self.vertices.align_to::<u8>().1,
+            );
+        }
+    }
+
     pub fn update(&mut self, width: i32, height: i32) {
         unsafe {
             self.projection = glm::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
@@ -500,6 +573,14 @@ impl Renderer {
         }
     }
 
+    /// Per channel blending for subpixel text drawn with [`lcd_shader`].
+    pub fn enable_lcd_blend(&mut self) {
+        unsafe {
+            self.gl.enable(glow::BLEND);
+            self.gl.blend_func(glow::ONE, glow::ONE_MINUS_SRC1_COLOR);
+        }
+    }
+
     pub fn disable_blend(&mut self) {
         unsafe {
             self.gl.disable(glow::BLEND);
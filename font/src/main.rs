@@ -10,8 +10,8 @@ fn main() {
         let (width, height, mut window, events, mut glfw, gl) = create_window();
         let mut rd = Renderer::new(gl, width, height);
 
-        // let atlas = load_font(&rd, include_bytes!("../JetBrainsMono.ttf"));
-        let atlas = load_font(&rd, include_bytes!("../CascadiaMono.ttf"));
+        // let mut atlas = load_font(&rd, include_bytes!("../JetBrainsMono.ttf"), 48);
+        let mut atlas = load_font(&rd, include_bytes!("../CascadiaMono.ttf"), 48);
 
         rd.enable_blend();
         // rd.texture(
@@ -0,0 +1,385 @@
+use crate::*;
+#[cfg(feature = "freetype")]
+use freetype::face::LoadFlag;
+#[cfg(feature = "freetype")]
+use freetype::ffi::{FT_Outline_Funcs, FT_Vector};
+#[cfg(feature = "freetype")]
+use std::ffi::{c_int, c_void};
+
+/// Maximum distance in pixels between a curve and the straight segments that replace it.
+pub const OUTLINE_TOLERANCE: f32 = 0.2;
+
+/// One step of a glyph outline, in font units with y going up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    /// Starts a new contour, contours are always closed.
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    /// Quadratic bezier with one control point, what TrueType outlines are made of.
+    ConicTo(Vec2, Vec2),
+    /// Cubic bezier, what CFF outlines are made of.
+    CubicTo(Vec2, Vec2, Vec2),
+}
+
+/// The vector outline of a glyph, filled with the nonzero winding rule.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlyphOutline {
+    pub segments: Vec<PathSegment>,
+}
+
+impl GlyphOutline {
+    /// Closed polygons approximating the outline, multiplied by `scale` so `tolerance` is in
+    /// the same units as the result.
+    pub fn flatten(&self, scale: f32, tolerance: f32) -> Vec<Vec<Vec2>> {
+        let mut contours: Vec<Vec<Vec2>> = Vec::new();
+        let mut pen = Vec2::default();
+
+        for segment in &self.segments {
+            match *segment {
+                PathSegment::MoveTo(to) => {
+                    pen = to * scale;
+                    contours.push(vec![pen]);
+                    continue;
+                }
+                _ if contours.is_empty() => contours.push(vec![pen]),
+                _ => {}
+            }
+            let contour = contours.last_mut().unwrap();
+
+            match *segment {
+                PathSegment::MoveTo(_) => unreachable!(),
+                PathSegment::LineTo(to) => {
+                    pen = to * scale;
+                    contour.push(pen);
+                }
+                PathSegment::ConicTo(control, to) => {
+                    let (p0, p1, p2) = (pen, control * scale, to * scale);
+                    //A segment of parameter length 1/n strays |p0 - 2p1 + p2| / 4n² from its chord.
+                    let steps = segments((p0 - p1 * 2.0 + p2).length() / (4.0 * tolerance));
+                    for i in 1..=steps {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        contour.push(p0 * (mt * mt) + p1 * (2.0 * mt * t) + p2 * (t * t));
+                    }
+                    pen = p2;
+                }
+                PathSegment::CubicTo(c1, c2, to) => {
+                    let (p0, p1, p2, p3) = (pen, c1 * scale, c2 * scale, to * scale);
+                    let bend = (p0 - p1 * 2.0 + p2)
+                        .length()
+                        .max((p1 - p2 * 2.0 + p3).length());
+                    let steps = segments(3.0 * bend / (4.0 * tolerance));
+                    for i in 1..=steps {
+                        let t = i as f32 / steps as f32;
+                        let mt = 1.0 - t;
+                        contour.push(
+                            p0 * (mt * mt * mt)
+                                + p1 * (3.0 * mt * mt * t)
+                                + p2 * (3.0 * mt * t * t)
+                                + p3 * (t * t * t),
+                        );
+                    }
+                    pen = p3;
+                }
+            }
+        }
+
+        contours.retain(|contour| contour.len() > 2);
+        contours
+    }
+
+    /// Fill triangles for the outline, see [`tessellate`].
+    pub fn tessellate(&self, scale: f32, tolerance: f32) -> Vec<Vec2> {
+        tessellate(&self.flatten(scale, tolerance))
+    }
+}
+
+/// Number of straight segments for a curve where `n²` of them are needed.
+fn segments(squared: f32) -> usize {
+    (squared.sqrt().ceil() as usize).clamp(1, 64)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    bottom: Vec2,
+    top: Vec2,
+    /// +1 for edges going up, -1 for edges going down.
+    winding: i32,
+}
+
+impl Edge {
+    fn x_at(&self, y: f32) -> f32 {
+        self.bottom.x
+            + (y - self.bottom.y) * (self.top.x - self.bottom.x) / (self.top.y - self.bottom.y)
+    }
+}
+
+/// Triangulate closed polygons with the nonzero winding rule, so overlapping contours and holes
+/// come out the same as when FreeType rasterizes them. Every three points are a counter
+/// clockwise triangle.
+///
+/// The polygons are cut into horizontal slabs at every vertex and every place two edges cross,
+/// then each slab is filled between the edges where the winding number isn't zero.
+///https://en.wikipedia.org/wiki/Nonzero-rule
+pub fn tessellate(contours: &[Vec<Vec2>]) -> Vec<Vec2> {
+    let mut edges = Vec::new();
+    for contour in contours {
+        for (i, &a) in contour.iter().enumerate() {
+            let b = contour[(i + 1) % contour.len()];
+            //Horizontal edges never cross the inside of a slab.
+            if a.y == b.y {
+                continue;
+            }
+            edges.push(if a.y < b.y {
+                Edge {
+                    bottom: a,
+                    top: b,
+                    winding: 1,
+                }
+            } else {
+                Edge {
+                    bottom: b,
+                    top: a,
+                    winding: -1,
+                }
+            });
+        }
+    }
+
+    let mut ys: Vec<f32> = edges.iter().flat_map(|e| [e.bottom.y, e.top.y]).collect();
+    ys.sort_by(f32::total_cmp);
+    ys.dedup();
+
+    let mut triangles = Vec::new();
+    for slab in ys.windows(2) {
+        let (y0, y1) = (slab[0], slab[1]);
+        let active: Vec<&Edge> = edges
+            .iter()
+            .filter(|e| e.bottom.y <= y0 && e.top.y >= y1)
+            .collect();
+
+        //Split the slab where edges cross so their order is the same all the way through.
+        let mut cuts = vec![y0, y1];
+        for (i, a) in active.iter().enumerate() {
+            for b in &active[i + 1..] {
+                let below = a.x_at(y0) - b.x_at(y0);
+                let above = a.x_at(y1) - b.x_at(y1);
+                if below * above < 0.0 {
+                    cuts.push(y0 + (y1 - y0) * below / (below - above));
+                }
+            }
+        }
+        cuts.sort_by(f32::total_cmp);
+        cuts.dedup();
+
+        for cut in cuts.windows(2) {
+            let (bottom, top) = (cut[0], cut[1]);
+            if top - bottom <= f32::EPSILON {
+                continue;
+            }
+
+            let mut spans: Vec<(f32, f32, i32)> = active
+                .iter()
+                .map(|e| (e.x_at(bottom), e.x_at(top), e.winding))
+                .collect();
+            spans.sort_by(|a, b| (a.0 + a.1).total_cmp(&(b.0 + b.1)));
+
+            let mut winding = 0;
+            let mut left = (0.0, 0.0);
+            for (x0, x1, w) in spans {
+                if winding == 0 {
+                    left = (x0, x1);
+                }
+                winding += w;
+                if winding == 0 {
+                    trapezoid(&mut triangles, bottom, top, left, (x0, x1));
+                }
+            }
+        }
+    }
+    triangles
+}
+
+/// Two triangles between the `left` and `right` edges, given as their x at `bottom` and `top`.
+fn trapezoid(
+    triangles: &mut Vec<Vec2>,
+    bottom: f32,
+    top: f32,
+    left: (f32, f32),
+    right: (f32, f32),
+) {
+    let bottom_left = Vec2::new(left.0, bottom);
+    let bottom_right = Vec2::new(right.0, bottom);
+    let top_right = Vec2::new(right.1, top);
+    let top_left = Vec2::new(left.1, top);
+
+    //One side collapses to a point on triangles.
+    if right.0 > left.0 {
+        triangles.extend([bottom_left, bottom_right, top_right]);
+    }
+    if right.1 > left.1 {
+        triangles.extend([top_right, top_left, bottom_left]);
+    }
+}
+
+impl Renderer {
+    /// Fill triangles from [`tessellate`] with their origin at `[x, y]`. Like [`Renderer::quad`]
+    /// this samples the solid corner of the bound atlas, so it can share a batch with text.
+    /// The edges are aliased unless the window was created with multisampling.
+    pub fn fill(&mut self, triangles: &[Vec2], x: f32, y: f32, color: Vec4) {
+        let offset = Vec2::new(x, y);
+        for &point in triangles {
+            self.vertex(point + offset, color, Vec2::default());
+        }
+    }
+}
+
+#[cfg(feature = "freetype")]
+impl FontFace {
+    /// Outline of glyph `id` in font units at the current design axes, `None` for bitmap glyphs.
+    pub fn outline(&self, id: u32, synthetic: Synthetic) -> Option<GlyphOutline> {
+        let face = self.face.as_ref()?;
+        face.load_glyph(
+            id,
+            LoadFlag::NO_SCALE | LoadFlag::NO_HINTING | LoadFlag::NO_BITMAP,
+        )
+        .ok()?;
+
+        unsafe {
+            let slot = face.raw().glyph;
+            if (*slot).format != freetype::ffi::FT_GLYPH_FORMAT_OUTLINE {
+                return None;
+            }
+            let outline = &mut (*slot).outline;
+
+            //The same strength and shear as FT_GlyphSlot_Embolden and FT_GlyphSlot_Oblique,
+            //which only work on scaled outlines.
+            if synthetic.bold {
+                let strength = face.raw().units_per_EM as freetype::ffi::FT_Pos / 24;
+                freetype::ffi::FT_Outline_Embolden(outline, strength);
+            }
+            if synthetic.oblique {
+                let shear = freetype::ffi::FT_Matrix {
+                    xx: 0x10000,
+                    xy: 0x0366A,
+                    yx: 0,
+                    yy: 0x10000,
+                };
+                freetype::ffi::FT_Outline_Transform(outline, &shear);
+            }
+
+            let funcs = FT_Outline_Funcs {
+                move_to,
+                line_to,
+                conic_to,
+                cubic_to,
+                shift: 0,
+                delta: 0,
+            };
+            let mut segments: Vec<PathSegment> = Vec::new();
+            let error = freetype::ffi::FT_Outline_Decompose(
+                outline,
+                &funcs,
+                &mut segments as *mut Vec<PathSegment> as *mut c_void,
+            );
+            (error == 0).then_some(GlyphOutline { segments })
+        }
+    }
+}
+
+#[cfg(feature = "freetype")]
+fn point(v: *const FT_Vector) -> Vec2 {
+    unsafe { Vec2::new((*v).x as f32, (*v).y as f32) }
+}
+
+#[cfg(feature = "freetype")]
+fn push(user: *mut c_void, segment: PathSegment) -> c_int {
+    unsafe { (*(user as *mut Vec<PathSegment>)).push(segment) };
+    0
+}
+
+#[cfg(feature = "freetype")]
+extern "C" fn move_to(to: *const FT_Vector, user: *mut c_void) -> c_int {
+    push(user, PathSegment::MoveTo(point(to)))
+}
+
+#[cfg(feature = "freetype")]
+extern "C" fn line_to(to: *const FT_Vector, user: *mut c_void) -> c_int {
+    push(user, PathSegment::LineTo(point(to)))
+}
+
+#[cfg(feature = "freetype")]
+extern "C" fn conic_to(
+    control: *const FT_Vector,
+    to: *const FT_Vector,
+    user: *mut c_void,
+) -> c_int {
+    push(user, PathSegment::ConicTo(point(control), point(to)))
+}
+
+#[cfg(feature = "freetype")]
+extern "C" fn cubic_to(
+    c1: *const FT_Vector,
+    c2: *const FT_Vector,
+    to: *const FT_Vector,
+    user: *mut c_void,
+) -> c_int {
+    push(user, PathSegment::CubicTo(point(c1), point(c2), point(to)))
+}
+
+#[cfg(feature = "freetype")]
+impl Atlas {
+    /// Outline of a glyph with [`Atlas::synthetic`] applied, see [`FontFace::outline`].
+    pub fn outline(&self, key: GlyphKey) -> Option<GlyphOutline> {
+        self.faces.get(key.face)?.outline(key.id, self.synthetic)
+    }
+
+    /// Fill triangles for `text` at `size` pixels per em, relative to the first baseline.
+    /// Nothing goes through the atlas, so it stays sharp at any size. Tessellating is much slower
+    /// than looking up a glyph, keep the result around for text that doesn't change.
+    pub fn tessellate_text(&self, text: &str, size: f32, options: TextOptions) -> Vec<Vec2> {
+        let options = TextOptions {
+            scale: size / self.size as f32,
+            ..options
+        };
+
+        let mut triangles = Vec::new();
+        for line in self.layout(text, options) {
+            for glyph in &line.glyphs {
+                let face = &self.faces[glyph.face];
+                let key = GlyphKey {
+                    face: glyph.face,
+                    id: glyph.id,
+                    subpixel: 0,
+                };
+                let Some(outline) = self.outline(key) else {
+                    continue;
+                };
+
+                let scale = size / face.shaper.face.units_per_em() as f32;
+                triangles.extend(
+                    outline
+                        .tessellate(scale, OUTLINE_TOLERANCE)
+                        .into_iter()
+                        .map(|point| point + glyph.position),
+                );
+            }
+        }
+        triangles
+    }
+
+    /// Draw `text` as filled outlines at `size` pixels per em, `[x, y]` is the first baseline.
+    /// For titles and zoomable canvases where the atlas would have to be huge.
+    pub fn draw_text_outline(
+        &self,
+        rd: &mut Renderer,
+        text: &str,
+        x: f32,
+        y: f32,
+        size: f32,
+        color: Vec4,
+    ) {
+        let triangles = self.tessellate_text(text, size, TextOptions::default());
+        rd.fill(&triangles, x, y, color);
+    }
+}
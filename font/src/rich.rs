@@ -0,0 +1,312 @@
+use crate::*;
+use std::borrow::{Borrow, BorrowMut};
+use std::ops::Range;
+
+/// A piece of text with its own style.
+///
+/// ```rs
+/// let spans = [
+///     Span::new("12:04 ", dim),
+///     Span::new("error", red).with_atlas(1).with_underline(),
+///     Span::new(": file not found", white),
+/// ];
+/// let batches = draw_rich_text(&mut rd, &mut [&mut regular, &mut bold], &spans, 25.0, 200.0, options);
+/// rd.draw_batches(&batches);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Span<'a> {
+    pub text: &'a str,
+    pub color: Vec4,
+    /// Index into the atlases the spans are drawn with.
+    pub atlas: usize,
+    /// Pixel size, `None` draws at the size the atlas was rasterized at. Any other size is a
+    /// scaled copy of the atlas glyphs and looks blurry unless the atlas is [`Mode::Sdf`],
+    /// use an atlas rasterized at this size instead.
+    pub size: Option<f32>,
+    pub underline: bool,
+}
+
+impl<'a> Span<'a> {
+    pub fn new(text: &'a str, color: Vec4) -> Self {
+        Self {
+            text,
+            color,
+            atlas: 0,
+            size: None,
+            underline: false,
+        }
+    }
+
+    pub fn with_atlas(mut self, atlas: usize) -> Self {
+        self.atlas = atlas;
+        self
+    }
+
+    /// Draw with the atlas for `style` when the atlases are [`FontFamily::atlases`].
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.atlas = style.index();
+        self
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+}
+
+/// Spans laid out as one block of text.
+#[derive(Debug, Clone, Default)]
+pub struct RichText {
+    /// Every span's text joined together, glyph clusters and line ranges index into this.
+    pub text: String,
+    /// Byte range of each span in `text`.
+    pub spans: Vec<Range<usize>>,
+    pub lines: Vec<Line>,
+}
+
+impl RichText {
+    /// Index of the span containing byte `index` of [`RichText::text`].
+    pub fn span_at(&self, index: usize) -> usize {
+        self.spans
+            .partition_point(|range| range.end <= index)
+            .min(self.spans.len().saturating_sub(1))
+    }
+}
+
+impl Atlas {
+    /// Distance below the baseline and thickness of the underline.
+    pub fn underline(&self) -> (f32, f32) {
+        let shaper = &self.faces[0].shaper;
+        match shaper.face.underline_metrics() {
+            Some(metrics) => (
+                metrics.position as f32 * shaper.scale,
+                (metrics.thickness as f32 * shaper.scale).max(1.0),
+            ),
+            //Roughly what most fonts use.
+            None => (
+                -(self.size as f32) / 10.0,
+                (self.size as f32 / 14.0).max(1.0),
+            ),
+        }
+    }
+}
+
+/// Multiplier from the atlas size to the span size.
+fn span_scale(atlas: &Atlas, span: &Span, options: TextOptions) -> f32 {
+    let size = span.size.map_or(1.0, |size| size / atlas.size as f32);
+    size * options.scale
+}
+
+/// Lay out `spans` like a single string. Wrapping can break inside or between spans and every
+/// line is tall enough for the largest span on it.
+pub fn layout_rich<A: Borrow<Atlas>>(
+    atlases: &[A],
+    spans: &[Span],
+    options: TextOptions,
+) -> RichText {
+    let mut rich = RichText::default();
+    for span in spans {
+        let start = rich.text.len();
+        rich.text.push_str(span.text);
+        rich.spans.push(start..rich.text.len());
+    }
+
+    if spans.is_empty() {
+        return rich;
+    }
+
+    //Each tab stops on the grid of the span it's in.
+    let tab_widths: Vec<f32> = spans
+        .iter()
+        .map(|span| {
+            let atlas = atlases[span.atlas].borrow();
+            let scale = span_scale(atlas, span, options);
+            atlas.tab_width(TextOptions { scale, ..options })
+        })
+        .collect();
+
+    let mut start = 0;
+    for paragraph in rich.text.split('\n') {
+        let next = start + paragraph.len() + '\n'.len_utf8();
+        let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+        let end = start + paragraph.len();
+        let levels = bidi_levels(paragraph);
+        let mut glyphs = Vec::new();
+
+        for (span, range) in spans.iter().zip(&rich.spans) {
+            let (from, to) = (range.start.max(start), range.end.min(end));
+            if from >= to {
+                continue;
+            }
+
+            let atlas = atlases[span.atlas].borrow();
+            let scale = span_scale(atlas, span, options);
+            let levels = &levels[from - start..to - start];
+            for mut glyph in atlas.shape_levels(&rich.text[from..to], levels, options) {
+                glyph.cluster += from - start;
+                glyph.advance = glyph.advance * scale;
+                glyph.offset = glyph.offset * scale;
+                glyphs.push(glyph);
+            }
+        }
+
+        //Baselines are filled in below once we know what's on each line.
+        let ranges = &rich.spans;
+        wrap(
+            &mut rich.lines,
+            0.0,
+            paragraph,
+            start,
+            &mut glyphs,
+            options.max_width,
+            |cluster| tab_widths[ranges.partition_point(|range| range.end <= start + cluster)],
+        );
+        start = next;
+    }
+
+    let mut y = 0.0;
+    let mut below_previous = None;
+    for i in 0..rich.lines.len() {
+        let line = &rich.lines[i];
+        let mut used: Vec<usize> = line
+            .glyphs
+            .iter()
+            .map(|glyph| rich.span_at(glyph.cluster))
+            .collect();
+        if used.is_empty() {
+            used.push(rich.span_at(line.range.start));
+        }
+
+        let (mut above, mut below): (f32, f32) = (0.0, 0.0);
+        for span in used {
+            let span = &spans[span];
+            let atlas = atlases[span.atlas].borrow();
+            let scale = span_scale(atlas, span, options);
+            let ascender = (atlas.ascender * scale).round();
+            let advance = atlas.line_advance(TextOptions { scale, ..options });
+            above = above.max(ascender);
+            below = below.max(advance - ascender);
+        }
+
+        if let Some(below_previous) = below_previous {
+            y -= below_previous + above;
+        }
+        below_previous = Some(below);
+
+        let line = &mut rich.lines[i];
+        line.y = y;
+        for glyph in &mut line.glyphs {
+            glyph.position.y += y;
+        }
+    }
+
+    align(&rich.text, &mut rich.lines, options);
+    rich
+}
+
+/// Draw `spans` with the first baseline at `[x, y]`. Each atlas has its own texture so the
+/// vertices are grouped by atlas, draw them with [`Renderer::draw_batches`].
+pub fn draw_rich_text<A: BorrowMut<Atlas>>(
+    rd: &mut Renderer,
+    atlases: &mut [A],
+    spans: &[Span],
+    x: f32,
+    y: f32,
+    options: TextOptions,
+) -> Vec<Batch> {
+    let rich = layout_rich(atlases, spans, options);
+    let mut batches = Vec::new();
+
+    for (index, atlas) in atlases.iter_mut().enumerate() {
+        let atlas = atlas.borrow_mut();
+        let start = rd.vertices.len();
+
+        for line in &rich.lines {
+            for glyph in &line.glyphs {
+                let span = &spans[rich.span_at(glyph.cluster)];
+                if span.atlas != index {
+                    continue;
+                }
+
+                let (pen, subpixel) = atlas.snap(x + glyph.position.x);
+                let ch = atlas.positioned_glyph(glyph, subpixel);
+
+                atlas.draw_glyph(
+                    rd,
+                    &ch,
+                    pen,
+                    (y + glyph.position.y).round(),
+                    span_scale(atlas, span, options),
+                    span.color,
+                );
+            }
+
+            draw_underlines(rd, atlas, index, &rich, line, spans, x, y, options);
+        }
+
+        if rd.vertices.len() > start {
+            batches.push(Batch {
+                texture: atlas.upload(rd.gl),
+                color: atlas.color_texture,
+                range: start..rd.vertices.len(),
+            });
+        }
+    }
+
+    batches
+}
+
+/// One rectangle per run of underlined glyphs from the same span, trailing spaces excluded.
+#[allow(clippy::too_many_arguments)]
+fn draw_underlines(
+    rd: &mut Renderer,
+    atlas: &Atlas,
+    index: usize,
+    rich: &RichText,
+    line: &Line,
+    spans: &[Span],
+    x: f32,
+    y: f32,
+    options: TextOptions,
+) {
+    let line_end = line.x + line.width;
+    let mut glyphs = line.glyphs.iter().peekable();
+
+    while let Some(first) = glyphs.next() {
+        let current = rich.span_at(first.cluster);
+        let span = &spans[current];
+
+        let mut end = first.position.x + first.advance.x;
+        while let Some(next) = glyphs.next_if(|glyph| rich.span_at(glyph.cluster) == current) {
+            end = next.position.x + next.advance.x;
+        }
+
+        if !span.underline || span.atlas != index {
+            continue;
+        }
+
+        let start = first.position.x;
+        let end = end.min(line_end);
+        if end <= start {
+            continue;
+        }
+
+        let scale = span_scale(atlas, span, options);
+        let (position, thickness) = atlas.underline();
+        let (position, thickness) = (position * scale, (thickness * scale).round().max(1.0));
+
+        atlas.draw_rect(
+            rd,
+            x + start,
+            y + line.y + (position - thickness / 2.0).round(),
+            end - start,
+            thickness,
+            span.color,
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::*;
+
+/// Empty texels around every glyph in a [`Mode::Sdf`] atlas, so shadows can be moved this far
+/// without being clipped by the quad or sampling the neighbouring glyphs.
+pub const SDF_MARGIN: i32 = 8;
+
+/// Surround a distance field with [`SDF_MARGIN`] texels that are as far outside as it goes.
+#[cfg(feature = "freetype")]
+pub(crate) fn pad_sdf(glyph: Glyph, buffer: &[u8]) -> (Glyph, Vec<u8>) {
+    if glyph.width == 0.0 || glyph.height == 0.0 {
+        return (glyph, buffer.to_vec());
+    }
+
+    let (width, height) = (glyph.width as usize, glyph.height as usize);
+    let margin = SDF_MARGIN as usize;
+    let stride = width + margin * 2;
+    let mut padded = vec![0; stride * (height + margin * 2)];
+    for (y, row) in buffer.chunks_exact(width).enumerate() {
+        let start = (y + margin) * stride + margin;
+        padded[start..start + width].copy_from_slice(row);
+    }
+
+    let glyph = Glyph {
+        width: stride as f32,
+        height: (height + margin * 2) as f32,
+        bearing: Vec2::new(
+            glyph.bearing.x - SDF_MARGIN as f32,
+            glyph.bearing.y + SDF_MARGIN as f32,
+        ),
+        ..glyph
+    };
+    (glyph, padded)
+}
+
+/// Effects for text drawn from a [`Mode::Sdf`] atlas with `sdf.frag`.
+/// Widths are in distance field units, where 0.5 is the edge of the glyph
+/// and 0.0 is [spread](https://freetype.org/freetype2/docs/reference/ft2-properties.html#spread) pixels outside of it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SdfStyle {
+    pub outline_color: Vec4,
+    pub outline_width: f32,
+    pub shadow_color: Vec4,
+    /// Offset of the shadow in atlas pixels with y going up, so it grows with
+    /// [`TextOptions::scale`]. Clamped to [`SDF_MARGIN`].
+    pub shadow_offset: Vec2,
+    pub shadow_softness: f32,
+    pub glow_color: Vec4,
+    pub glow_width: f32,
+}
+
+impl SdfStyle {
+    /// Upload the style to the renderer's current shader.
+    pub fn apply(&self, rd: &Renderer) {
+        unsafe {
+            let gl = rd.gl;
+            let location = |name| gl.get_uniform_location(rd.shader, name);
+            let vec4 =
+                |name, v: Vec4| gl.uniform_4_f32(location(name).as_ref(), v.x, v.y, v.z, v.w);
+
+            vec4("outline_color", self.outline_color);
+            gl.uniform_1_f32(location("outline_width").as_ref(), self.outline_width);
+
+            vec4("shadow_color", self.shadow_color);
+            let margin = SDF_MARGIN as f32;
+            gl.uniform_2_f32(
+                location("shadow_offset").as_ref(),
+                self.shadow_offset.x.clamp(-margin, margin),
+                self.shadow_offset.y.clamp(-margin, margin),
+            );
+            gl.uniform_1_f32(location("shadow_softness").as_ref(), self.shadow_softness);
+
+            vec4("glow_color", self.glow_color);
+            gl.uniform_1_f32(location("glow_width").as_ref(), self.glow_width);
+        }
+    }
+}
+
+/// Shader for [`Mode::Sdf`] atlases, use it with [`Renderer::use_shader`] then [`SdfStyle::apply`].
+pub fn sdf_shader() -> glow::NativeProgram {
+    shader! {
+        include_str!("../shaders/simple.vert"),
+        include_str!("../shaders/sdf.frag"),
+        Vec2 => 0,
+        Vec2 => 1,
+        Vec4 => 2
+    }
+}
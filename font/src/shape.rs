@@ -0,0 +1,223 @@
+use crate::*;
+use rustybuzz::{Direction, Feature, GlyphBuffer, Tag, UnicodeBuffer};
+use std::ops::Range;
+use unicode_bidi::{BidiInfo, Level};
+
+/// A glyph placed by the shaper. Offsets and advances are in pixels.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShapedGlyph {
+    /// Index into [`Atlas::faces`].
+    pub face: usize,
+    pub id: u32,
+    /// Byte offset into the source text of the first character that produced this glyph.
+    /// Ligatures cover every character up to the next cluster.
+    pub cluster: usize,
+    pub advance: Vec2,
+    pub offset: Vec2,
+    /// Bidi embedding level, odd levels are right to left.
+    pub level: u8,
+}
+
+/// Turns text into positioned glyph ids, applying ligatures, contextual alternates,
+/// kerning and mark positioning from the font's `GSUB` and `GPOS` tables.
+///
+///https://harfbuzz.github.io/what-is-harfbuzz.html
+pub struct Shaper {
+    pub face: rustybuzz::Face<'static>,
+    /// Pixels per font unit.
+    pub scale: f32,
+}
+
+impl std::fmt::Debug for Shaper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Shaper")
+            .field("scale", &self.scale)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Shaper {
+    pub fn new(font: &'static [u8], pixels_per_em: f32) -> Self {
+        let face = rustybuzz::Face::from_slice(font, 0).unwrap();
+        let scale = pixels_per_em / face.units_per_em() as f32;
+        Self { face, scale }
+    }
+
+    /// Shape a single line of text. Missing characters come back as glyph 0 (`.notdef`).
+    pub fn shape(&self, line: &str, options: TextOptions) -> Vec<ShapedGlyph> {
+        self.shape_buffer(line, None, options)
+    }
+
+    /// Shape text that all goes in one direction. Right to left runs come back in visual order,
+    /// with paired punctuation like '(' mirrored.
+    pub fn shape_run(&self, run: &str, rtl: bool, options: TextOptions) -> Vec<ShapedGlyph> {
+        let direction = if rtl {
+            Direction::RightToLeft
+        } else {
+            Direction::LeftToRight
+        };
+        self.shape_buffer(run, Some(direction), options)
+    }
+
+    fn shape_buffer(
+        &self,
+        text: &str,
+        direction: Option<Direction>,
+        options: TextOptions,
+    ) -> Vec<ShapedGlyph> {
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        if let Some(direction) = direction {
+            buffer.set_direction(direction);
+        }
+
+        let mut features = Vec::new();
+        if !options.kerning {
+            features.push(Feature::new(Tag::from_bytes(b"kern"), 0, ..));
+        }
+
+        let output = rustybuzz::shape(&self.face, &features, buffer);
+        self.glyphs(&output)
+    }
+
+    fn glyphs(&self, output: &GlyphBuffer) -> Vec<ShapedGlyph> {
+        //Fractional, glyphs are snapped to pixels or subpixel offsets when they're drawn.
+        let px = |units: i32| units as f32 * self.scale;
+
+        output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .map(|(info, position)| ShapedGlyph {
+                face: 0,
+                id: info.glyph_id,
+                cluster: info.cluster as usize,
+                advance: Vec2::new(px(position.x_advance), px(position.y_advance)),
+                offset: Vec2::new(px(position.x_offset), px(position.y_offset)),
+                level: 0,
+            })
+            .collect()
+    }
+}
+
+/// Bidi embedding level of every byte in `paragraph`, the base direction comes from the first
+/// strong character.
+pub fn bidi_levels(paragraph: &str) -> Vec<Level> {
+    BidiInfo::new(paragraph, None).levels
+}
+
+/// Visual order of a line from the levels of its glyphs in logical order, rule L2 of UAX #9.
+///
+///https://www.unicode.org/reports/tr9/#L2
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let Some(lowest_odd) = levels.iter().copied().filter(|level| level % 2 == 1).min() else {
+        return order;
+    };
+
+    //Reverse every run at or above each level, from the highest down to the lowest odd one.
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+
+    order
+}
+
+impl Atlas {
+    /// Split `line` into runs drawn by the same face in the same direction and shape each of them.
+    /// Glyphs come back in logical order, see [`visual_order`].
+    pub fn shape(&self, line: &str, options: TextOptions) -> Vec<ShapedGlyph> {
+        self.shape_levels(line, &bidi_levels(line), options)
+    }
+
+    /// Same as [`Atlas::shape`] with the bidi levels already resolved, `levels` has one entry per
+    /// byte of `line`. Used when `line` is only part of a paragraph.
+    pub fn shape_levels(
+        &self,
+        line: &str,
+        levels: &[Level],
+        options: TextOptions,
+    ) -> Vec<ShapedGlyph> {
+        let mut glyphs = Vec::new();
+        for (face, level, range) in self.runs(line, levels) {
+            let space = self.faces[face]
+                .shaper
+                .face
+                .glyph_index(' ')
+                .map_or(0, |id| id.0 as u32);
+
+            let mut run =
+                self.faces[face]
+                    .shaper
+                    .shape_run(&line[range.clone()], level.is_rtl(), options);
+            if level.is_rtl() {
+                run.reverse();
+            }
+
+            for mut glyph in run {
+                glyph.face = face;
+                glyph.cluster += range.start;
+                glyph.level = level.number();
+
+                //The shaper already hides format characters like U+200B, but control characters
+                //come back as .notdef. Tabs are stretched during layout.
+                if line[glyph.cluster..].starts_with(char::is_control) {
+                    glyph.id = space;
+                    glyph.advance = Vec2::default();
+                    glyph.offset = Vec2::default();
+                }
+                //Emboldened glyphs are wider, marks still don't take up any room.
+                if glyph.advance.x != 0.0 {
+                    glyph.advance.x += self.options.embolden;
+                }
+
+                glyphs.push(glyph);
+            }
+        }
+        glyphs
+    }
+
+    /// Byte ranges of `line` with the index of the first face that can draw them and their level.
+    fn runs(&self, line: &str, levels: &[Level]) -> Vec<(usize, Level, Range<usize>)> {
+        let mut runs: Vec<(usize, Level, Range<usize>)> = Vec::new();
+
+        for (i, c) in line.char_indices() {
+            let current = runs.last().map(|(face, _, _)| *face);
+
+            //Spaces, punctuation and combining marks stay in the current run when they can,
+            //otherwise every space in a run of symbols would switch back to the primary face.
+            let face = match current {
+                Some(face) if !c.is_alphanumeric() && self.faces[face].contains(c) => face,
+                _ => self
+                    .faces
+                    .iter()
+                    .position(|face| face.contains(c))
+                    .or(current)
+                    .unwrap_or(0),
+            };
+
+            let level = levels[i];
+            let end = i + c.len_utf8();
+            match runs.last_mut() {
+                Some((f, l, range)) if *f == face && *l == level => range.end = end,
+                _ => runs.push((face, level, i..end)),
+            }
+        }
+
+        runs
+    }
+}
@@ -55,3 +55,1082 @@ pub fn pixel_perfect() {
         glfw.poll_events();
     }
 }
+
+#[test]
+pub fn packer() {
+    let mut packer = Packer::new(64, 32);
+
+    assert_eq!(packer.pack(30, 10), Some((0, 0)));
+    assert_eq!(packer.pack(30, 8), Some((31, 0)));
+    //Doesn't fit on the first shelf anymore.
+    assert_eq!(packer.pack(30, 8), Some((0, 11)));
+    //Too tall for any shelf and there is no room for a new one.
+    assert_eq!(packer.pack(10, 20), None);
+
+    packer.grow(64);
+    assert_eq!(packer.pack(10, 20), Some((0, 20)));
+    assert_eq!(packer.pack(65, 1), None);
+}
+
+#[test]
+pub fn ligatures() {
+    let shaper = Shaper::new(include_bytes!("../CascadiaCode.ttf"), 48.0);
+    let options = TextOptions::default();
+
+    let arrow = shaper.shape("=>", options);
+    let equals = shaper.shape("=", options);
+    let greater = shaper.shape(">", options);
+
+    assert_eq!(arrow.len(), 2);
+    assert_ne!((arrow[0].id, arrow[1].id), (equals[0].id, greater[0].id));
+
+    //Cascadia Mono is the same font without the ligatures.
+    let shaper = Shaper::new(include_bytes!("../CascadiaMono.ttf"), 48.0);
+    let arrow = shaper.shape("=>", options);
+    let equals = shaper.shape("=", options);
+    let greater = shaper.shape(">", options);
+    assert_eq!((arrow[0].id, arrow[1].id), (equals[0].id, greater[0].id));
+}
+
+#[test]
+pub fn kerning() {
+    let shaper = Shaper::new(include_bytes!("../Antonio-Regular.ttf"), 48.0);
+    let width = |options: TextOptions| -> f32 {
+        shaper
+            .shape("AV", options)
+            .iter()
+            .map(|glyph| glyph.advance.x)
+            .sum()
+    };
+
+    let kerned = width(TextOptions::default());
+    let unkerned = width(TextOptions {
+        kerning: false,
+        ..Default::default()
+    });
+    assert!(kerned < unkerned, "{kerned} vs {unkerned}");
+}
+
+#[test]
+pub fn span_at() {
+    let rich = RichText {
+        text: "ab cd".to_string(),
+        spans: vec![0..2, 2..2, 2..5],
+        lines: Vec::new(),
+    };
+
+    assert_eq!(rich.span_at(0), 0);
+    assert_eq!(rich.span_at(1), 0);
+    //Empty spans never own a glyph.
+    assert_eq!(rich.span_at(2), 2);
+    assert_eq!(rich.span_at(4), 2);
+}
+
+#[test]
+pub fn invisible() {
+    let shaper = Shaper::new(include_bytes!("../CascadiaMono.ttf"), 48.0);
+    let glyphs = shaper.shape("a\u{200B}b\u{FEFF}", TextOptions::default());
+
+    assert_eq!(glyphs.len(), 4);
+    assert_eq!(glyphs[1].advance.x, 0.0);
+    assert_eq!(glyphs[3].advance.x, 0.0);
+    assert_ne!(glyphs[1].id, 0);
+}
+
+#[test]
+pub fn tabs() {
+    let glyph = |advance: f32, cluster: usize| ShapedGlyph {
+        cluster,
+        advance: Vec2::new(advance, 0.0),
+        ..Default::default()
+    };
+    let mut glyphs = [glyph(10.0, 0), glyph(0.0, 1), glyph(10.0, 2), glyph(0.0, 3)];
+    expand_tabs("a\tb\t", &mut glyphs, |_| 32.0);
+
+    assert_eq!(glyphs[1].advance.x, 22.0);
+    assert_eq!(glyphs[3].advance.x, 22.0);
+
+    //A tab that starts on a stop still moves to the next one.
+    let mut glyphs = [glyph(0.0, 0)];
+    expand_tabs("\t", &mut glyphs, |_| 32.0);
+    assert_eq!(glyphs[0].advance.x, 32.0);
+}
+
+#[test]
+pub fn bidi() {
+    //Hebrew letters are 1, the embedded number is 2.
+    assert_eq!(visual_order(&[0, 0, 1, 1, 1]), [0, 1, 4, 3, 2]);
+    assert_eq!(visual_order(&[1, 1, 2, 2, 1]), [4, 2, 3, 1, 0]);
+    assert_eq!(visual_order(&[0, 0]), [0, 1]);
+
+    let levels: Vec<u8> = bidi_levels("ab \u{5D0}\u{5D1}")
+        .iter()
+        .map(|level| level.number())
+        .collect();
+    assert_eq!(levels, [0, 0, 0, 1, 1, 1, 1]);
+
+    //Brackets are mirrored in right to left runs.
+    let shaper = Shaper::new(include_bytes!("../CascadiaMono.ttf"), 48.0);
+    let options = TextOptions::default();
+    let rtl = shaper.shape_run("(", true, options);
+    let ltr = shaper.shape("(", options);
+    let closing = shaper.shape(")", options);
+    assert_ne!(rtl[0].id, ltr[0].id);
+    assert_eq!(rtl[0].id, closing[0].id);
+}
+
+#[cfg(feature = "freetype")]
+fn cascadia(size: u32) -> Atlas {
+    let lib = freetype::Library::init().unwrap();
+    Atlas::new(
+        &lib,
+        include_bytes!("../CascadiaMono.ttf"),
+        size,
+        Mode::Coverage,
+    )
+}
+
+#[cfg(feature = "freetype")]
+#[test]
+pub fn measure() {
+    let atlas = cascadia(24);
+    let text = "one\ntwo three\n\nfour";
+    let metrics = atlas.measure(text);
+    let lines = atlas.layout(text, TextOptions::default());
+
+    assert_eq!(metrics.line_count(), 4);
+    assert_eq!(metrics.line_count(), lines.len());
+    let widths: Vec<f32> = lines.iter().map(|line| line.width).collect();
+    assert_eq!(metrics.lines, widths);
+    assert_eq!(metrics.width, atlas.measure("two three").width);
+    assert_eq!(
+        metrics.height,
+        atlas.line_advance(TextOptions::default()) * 4.0
+    );
+    assert_eq!(metrics.baseline, atlas.ascender);
+    assert_eq!(atlas.measure("").width, 0.0);
+
+    //Wrapping is measured the same way it's drawn.
+    let options = TextOptions {
+        max_width: Some(metrics.width / 2.0),
+        ..Default::default()
+    };
+    assert_eq!(
+        atlas.measure_with(text, options).line_count(),
+        atlas.layout(text, options).len()
+    );
+}
+
+#[cfg(feature = "freetype")]
+#[test]
+pub fn line_metrics() {
+    let atlas = cascadia(24);
+    assert_eq!(
+        atlas.line_height,
+        atlas.ascender - atlas.descender + atlas.line_gap
+    );
+    assert!(atlas.ascender > 0.0 && atlas.descender < 0.0);
+    assert_eq!(atlas.units_per_em, 2048);
+
+    let options = TextOptions::default();
+    assert_eq!(atlas.line_advance(options), atlas.line_height);
+    let double = TextOptions {
+        line_spacing: 2.0,
+        ..Default::default()
+    };
+    assert_eq!(atlas.line_advance(double), atlas.line_height * 2.0);
+    let lines = atlas.layout("a\nb\nc", double);
+    assert_eq!(lines[2].y, -atlas.line_height * 4.0);
+    assert_eq!(
+        atlas.measure_with("a\nb", double).height,
+        atlas.line_height * 4.0
+    );
+}
+
+#[cfg(feature = "freetype")]
+#[test]
+pub fn layout() {
+    let atlas = cascadia(32);
+    let advance = atlas.measure("a").width;
+
+    let options = TextOptions {
+        max_width: Some(advance * 6.0),
+        ..Default::default()
+    };
+    let lines = atlas.layout("aaaa bbbb", options);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].range, 0..5);
+    assert_eq!(lines[0].width, advance * 4.0);
+    assert_eq!(lines[1].range, 5..9);
+    assert_eq!(lines[1].y, -atlas.line_advance(options));
+
+    //"\r\n" is a single line break and the '\r' isn't part of the line.
+    let metrics = atlas.measure("ab\r\nc");
+    assert_eq!(metrics.lines, [advance * 2.0, advance]);
+    assert_eq!(metrics.height, atlas.line_height * 2.0);
+
+    let centered = atlas.layout(
+        "aa\na",
+        TextOptions {
+            align: Align::Center,
+            ..Default::default()
+        },
+    );
+    assert_eq!(centered[1].x, (advance / 2.0).round());
+
+    //Tab stops start over on every wrapped line.
+    let lines = atlas.layout("aaaaa a\tb", options);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].glyphs[2].position.x, atlas.tab_width(options));
+}
+
+#[test]
+pub fn atlas_bitmap() {
+    let mut bitmap = AtlasBitmap::with_size(64, 16, Mode::Coverage);
+    bitmap.resized = false;
+
+    let glyph = Glyph {
+        width: 40.0,
+        height: 10.0,
+        ..Default::default()
+    };
+    let first = GlyphKey {
+        face: 0,
+        id: 1,
+        subpixel: 0,
+    };
+    let second = GlyphKey {
+        face: 0,
+        id: 2,
+        subpixel: 0,
+    };
+
+    bitmap.insert(first, glyph, &[255; 400]).unwrap();
+    assert_eq!(bitmap.dirty, Some(0..10));
+    assert!(!bitmap.resized);
+
+    //Doesn't fit next to or under the first one, so the bitmap doubles.
+    let placed = bitmap.insert(second, glyph, &[255; 400]).unwrap();
+    assert_eq!((placed.x, placed.y), (0, 11));
+    assert_eq!(bitmap.height, 32);
+    assert!(bitmap.resized);
+    assert_eq!(bitmap.glyphs[&first].uv.v1, 10.0 / 32.0);
+    assert_eq!(bitmap.pixels[11 * 64], 255);
+
+    bitmap.max_height = 32;
+    assert!(bitmap.insert(first, glyph, &[255; 400]).is_none());
+}
+
+#[cfg(feature = "freetype")]
+#[test]
+pub fn bake() {
+    let font = include_bytes!("../CascadiaMono.ttf");
+    let mut atlas = cascadia(24);
+    atlas.preload("héllo → wörld");
+
+    let baked =
+        Atlas::from_baked(&atlas.bake_png().unwrap(), &atlas.bake_metrics(), &[font]).unwrap();
+    assert_eq!(baked.line_height, atlas.line_height);
+    assert_eq!(baked.fallback, atlas.fallback);
+    assert_eq!(baked.bitmap.pixels, atlas.bitmap.pixels);
+    assert_eq!(baked.bitmap.glyphs.len(), atlas.bitmap.glyphs.len());
+    for (key, glyph) in &atlas.bitmap.glyphs {
+        assert_eq!(baked.bitmap.glyphs[key].uv, glyph.uv);
+        assert_eq!(baked.bitmap.glyphs[key].advance, glyph.advance);
+    }
+
+    let png = atlas.bake_png().unwrap();
+    let metrics = atlas.bake_metrics();
+    assert!(Atlas::from_baked(&png, b"ATLS", &[font]).is_err());
+    assert!(Atlas::from_baked(&png, &metrics, &[]).is_err());
+    assert!(Atlas::from_baked(&png, &metrics[..metrics.len() - 1], &[font]).is_err());
+    assert!(Atlas::from_baked(&metrics, &metrics, &[font]).is_err());
+
+    let mut newer = metrics.clone();
+    newer[4] += 1;
+    assert!(Atlas::from_baked(&png, &newer, &[font]).is_err());
+
+    //Corrupt sizes are errors instead of huge allocations, the width starts at byte 11.
+    let with = |offset: usize, value: i32| {
+        let mut corrupt = metrics.clone();
+        corrupt[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        Atlas::from_baked(&png, &corrupt, &[font])
+    };
+    assert!(with(11, -1).is_err());
+    assert!(with(11, i32::MAX).is_err());
+    assert!(with(15, 0).is_err());
+    assert!(with(15, MAX_ATLAS_HEIGHT + 1).is_err());
+    //The last glyph ends with its x and y.
+    let last = metrics.len() - 8;
+    assert!(with(last, -1).is_err());
+    assert!(with(last, ATLAS_WIDTH + 1).is_err());
+    assert!(with(last + 4, i32::MAX).is_err());
+
+    //The texture has to be the one saved with the metrics.
+    let mut other = std::io::Cursor::new(Vec::new());
+    image::GrayImage::new(1, 1)
+        .write_to(&mut other, image::ImageOutputFormat::Png)
+        .unwrap();
+    let other = other.into_inner();
+    assert!(Atlas::from_baked(&other, &metrics, &[font]).is_err());
+}
+
+#[test]
+pub fn database() {
+    let db = FontDatabase::new();
+    let family = |name: &str| db.query(name).map(|font| font.family.as_str());
+
+    assert_eq!(family("Cascadia Code"), Some("Cascadia Code"));
+    assert_eq!(family("cascadia-mono"), Some("Cascadia Mono"));
+    assert_eq!(family("JetBrains Mono Bold Italic"), Some("JetBrains Mono"));
+    assert_eq!(family("Comic Sans"), None);
+
+    let mut db = FontDatabase::new();
+    assert_eq!(db.load("Comic Sans"), BUNDLED_FONTS[0]);
+    assert_eq!(db.load("JetBrains Mono"), BUNDLED_FONTS[2]);
+}
+
+#[cfg(unix)]
+#[test]
+pub fn database_scan() {
+    let dir = std::env::temp_dir().join(format!("font-scan-{}", std::process::id()));
+    let nested = dir.join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(nested.join("Cascadia.TTF"), BUNDLED_FONTS[0]).unwrap();
+    std::fs::write(nested.join("notes.txt"), "not a font").unwrap();
+    //A link back up the tree is skipped instead of scanned forever.
+    let _ = std::os::unix::fs::symlink(&dir, nested.join("loop"));
+
+    let mut db = FontDatabase::default();
+    db.scan(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(db.fonts.len(), 1);
+    assert_eq!(db.fonts[0].family, "Cascadia Mono");
+}
+
+/// Sum of the coverage of `c`'s bitmap.
+#[cfg(feature = "freetype")]
+fn ink(atlas: &mut Atlas, c: char) -> u32 {
+    let id = atlas.faces[0].shaper.face.glyph_index(c).unwrap().0 as u32;
+    let glyph = atlas
+        .glyph(GlyphKey {
+            face: 0,
+            id,
+            subpixel: 0,
+        })
+        .unwrap();
+    let row = atlas.bitmap.width as usize;
+    (0..glyph.height as usize)
+        .flat_map(|y| {
+            let start = (glyph.y as usize + y) * row + glyph.x as usize;
+            &atlas.bitmap.pixels[start..start + glyph.width as usize]
+        })
+        .map(|pixel| *pixel as u32)
+        .sum()
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn variations() {
+    let font = include_bytes!("../CascadiaCode.ttf");
+    let lib = freetype::Library::init().unwrap();
+    let mut atlas = Atlas::new(&lib, font, 32, Mode::Coverage);
+    let wght = atlas.axes()[0];
+    assert_eq!(&wght.tag.to_bytes(), b"wght");
+
+    //Thicker stems cover more pixels.
+    let regular = ink(&mut atlas, 'l');
+    atlas.set_variations(&[Variation::weight(wght.max_value)]);
+    let bold = ink(&mut atlas, 'l');
+    assert!(bold > regular);
+
+    //Out of range values are clamped and baked atlases shape with the same axes.
+    atlas.set_variations(&[Variation::weight(10_000.0)]);
+    assert_eq!(ink(&mut atlas, 'l'), bold);
+    let baked =
+        Atlas::from_baked(&atlas.bake_png().unwrap(), &atlas.bake_metrics(), &[font]).unwrap();
+    assert_eq!(baked.variations, atlas.variations);
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn family() {
+    let regular = include_bytes!("../CascadiaMono.ttf");
+    let lib = freetype::Library::init().unwrap();
+    let mut family = FontFamily::new(&lib, 32, Mode::Coverage, regular, None, None, None);
+
+    assert_eq!(family.get(Style::Regular).synthetic, Synthetic::default());
+    let both = family.get(Style::BoldItalic).synthetic;
+    assert!(both.bold && both.oblique);
+
+    let options = FontOptions {
+        hinting: Hinting::Light,
+        ..Default::default()
+    };
+    let light =
+        FontFamily::new_with_options(&lib, 16, Mode::Coverage, regular, None, None, None, options);
+    assert!(light.atlases.iter().all(|atlas| atlas.options == options));
+
+    let [regular, bold, italic, _] = &mut family.atlases;
+    assert!(ink(bold, 'l') > ink(regular, 'l'));
+
+    //Slanting moves the top of the stem right, so the bitmap gets wider.
+    let width = |atlas: &mut Atlas| {
+        let id = atlas.faces[0].shaper.face.glyph_index('I').unwrap().0 as u32;
+        atlas
+            .glyph(GlyphKey {
+                face: 0,
+                id,
+                subpixel: 0,
+            })
+            .unwrap()
+            .width
+    };
+    assert!(width(italic) > width(regular));
+
+    //Synthetic styles keep the advances of the face they came from.
+    assert_eq!(
+        family.measure("bold", Style::Bold),
+        family.measure("bold", Style::Regular)
+    );
+    assert_eq!(
+        Span::new("", Vec4::default())
+            .with_style(Style::Italic)
+            .atlas,
+        2
+    );
+}
+
+#[test]
+pub fn carets() {
+    let glyph = |cluster: usize, x: f32, level: u8| PositionedGlyph {
+        cluster,
+        position: Vec2::new(x, 0.0),
+        advance: Vec2::new(10.0, 0.0),
+        level,
+        ..Default::default()
+    };
+
+    //"ab" then two right to left letters, drawn as "ab" followed by the second then the first.
+    let text = "ab\u{5D0}\u{5D1}";
+    let line = Line {
+        glyphs: vec![
+            glyph(0, 0.0, 0),
+            glyph(1, 10.0, 0),
+            glyph(4, 20.0, 1),
+            glyph(2, 30.0, 1),
+        ],
+        range: 0..text.len(),
+        ..Default::default()
+    };
+
+    assert_eq!(hit_line(text, &line, -5.0), (0, None));
+    assert_eq!(hit_line(text, &line, 3.0), (0, Some(0)));
+    assert_eq!(hit_line(text, &line, 8.0), (1, Some(0)));
+    //The left half of a right to left letter is after it.
+    assert_eq!(hit_line(text, &line, 33.0), (4, Some(2)));
+    assert_eq!(hit_line(text, &line, 38.0), (2, Some(2)));
+    assert_eq!(hit_line(text, &line, 50.0), (2, None));
+
+    assert_eq!(caret_x(text, &line, 1), 10.0);
+    assert_eq!(caret_x(text, &line, 2), 40.0);
+    assert_eq!(caret_x(text, &line, 4), 30.0);
+    assert_eq!(caret_x(text, &line, 6), 20.0);
+
+    //A ligature is split between the characters it replaced.
+    let line = Line {
+        glyphs: vec![PositionedGlyph {
+            advance: Vec2::new(20.0, 0.0),
+            ..Default::default()
+        }],
+        range: 0..2,
+        ..Default::default()
+    };
+    assert_eq!(hit_line("=>", &line, 12.0), (1, Some(1)));
+    assert_eq!(caret_x("=>", &line, 1), 10.0);
+
+    //The accent stays with its letter.
+    let text = "e\u{301}";
+    let line = Line {
+        glyphs: vec![PositionedGlyph {
+            advance: Vec2::new(10.0, 0.0),
+            ..Default::default()
+        }],
+        range: 0..text.len(),
+        ..Default::default()
+    };
+    assert_eq!(hit_line(text, &line, 4.0), (0, Some(0)));
+    assert_eq!(hit_line(text, &line, 6.0), (3, Some(0)));
+    assert_eq!(caret_x(text, &line, 3), 10.0);
+    assert_eq!(selection_spans(text, &line, 0..3), [(0.0, 10.0)]);
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn hit_test() {
+    let atlas = cascadia(32);
+    let advance = atlas.measure("a").width;
+    let options = TextOptions::default();
+    let next_line = -atlas.line_advance(options);
+
+    let text = "ab\ncd";
+    let hit = atlas.hit_test(text, Vec2::new(advance * 0.6, 0.0), options);
+    assert_eq!(
+        hit,
+        Hit {
+            line: 0,
+            index: 1,
+            character: Some(0)
+        }
+    );
+
+    let hit = atlas.hit_test(text, Vec2::new(advance * 1.2, next_line), options);
+    assert_eq!(
+        hit,
+        Hit {
+            line: 1,
+            index: 4,
+            character: Some(4)
+        }
+    );
+
+    //Past the end of the line and below the last one.
+    let hit = atlas.hit_test(text, Vec2::new(advance * 10.0, next_line * 5.0), options);
+    assert_eq!(
+        hit,
+        Hit {
+            line: 1,
+            index: 5,
+            character: None
+        }
+    );
+
+    let caret = atlas.caret(text, 4, options);
+    assert_eq!((caret.line, caret.x), (1, advance));
+    assert_eq!(caret.y, next_line + atlas.descender);
+    assert_eq!(atlas.caret(text, 2, options).x, advance * 2.0);
+}
+
+#[test]
+pub fn text_field() {
+    let mut field = TextField::new().with_text("hello world");
+
+    //Typing is undone in one step.
+    field.insert("!");
+    field.insert("!");
+    assert_eq!(field.text, "hello world!!");
+    field.undo();
+    assert_eq!(field.text, "hello world");
+
+    field.redo();
+    assert_eq!((field.text.as_str(), field.caret), ("hello world!!", 13));
+
+    field.move_left(false, true);
+    assert_eq!(field.caret, 11);
+    field.move_left(true, true);
+    assert_eq!(field.selected_text(), "world");
+    field.insert("there");
+    assert_eq!(field.text, "hello there!!");
+
+    field.set_caret(field.text.len(), false);
+    field.backspace(true);
+    assert_eq!(field.text, "hello there");
+    field.set_caret(0, false);
+    field.delete(true);
+    assert_eq!(field.text, " there");
+    field.undo();
+    field.undo();
+    assert_eq!(field.text, "hello there!!");
+
+    //Single line fields turn line breaks into spaces.
+    field.select_all();
+    field.insert("a\r\nb");
+    assert_eq!(field.text, "a b");
+
+    let mut field = TextField::new().with_multiline(100.0).with_text("é");
+    field.move_left(false, false);
+    assert_eq!(field.caret, 0);
+    field.insert("a\r\nb");
+    assert_eq!(field.text, "a\nbé");
+
+    let mut field = TextField::new().with_text("ae\u{301}");
+    field.move_left(false, false);
+    assert_eq!(field.caret, 1);
+    field.move_right(false, false);
+    assert_eq!(field.caret, 4);
+    field.backspace(false);
+    assert_eq!(field.text, "a");
+
+    //Only nonspacing marks are kept together, the caret can stop inside a ZWJ sequence.
+    let zwj = "\u{1F469}\u{200D}\u{1F4BB}";
+    let mut field = TextField::new().with_text(zwj);
+    field.move_left(false, false);
+    assert_eq!(field.caret, zwj.len() - '\u{1F4BB}'.len_utf8());
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn text_field_mouse() {
+    let atlas = cascadia(32);
+    let advance = atlas.measure("a").width;
+    let mut field = TextField::new()
+        .with_multiline(advance * 20.0)
+        .with_text("abc\nde");
+    let next_line = -atlas.line_advance(field.layout_options());
+
+    field.click(&atlas, Vec2::new(advance * 1.2, 0.0), false);
+    field.drag(&atlas, Vec2::new(advance * 0.9, next_line));
+    field.release();
+    assert_eq!(field.selected_text(), "bc\nd");
+    assert!(field.contains(&atlas, Vec2::new(advance, next_line)));
+    assert!(!field.contains(&atlas, Vec2::new(advance, next_line * 3.0)));
+
+    //The caret keeps its column when passing through a shorter line.
+    let mut field = TextField::new()
+        .with_multiline(advance * 20.0)
+        .with_text("abcd\na\nabcd");
+    field.set_caret(3, false);
+    field.move_vertical(&atlas, 1, false);
+    assert_eq!(field.caret, 6);
+    field.move_vertical(&atlas, 1, false);
+    assert_eq!(field.caret, 10);
+    field.move_home(&atlas, true);
+    assert_eq!(field.selected_text(), "abc");
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn text_field_scroll() {
+    let atlas = cascadia(32);
+    let advance = atlas.measure("a").width;
+    let mut field = TextField::new()
+        .with_width(advance * 5.0)
+        .with_text(&"a".repeat(20));
+
+    //The caret at the end is pulled into view, clicks land on the scrolled text.
+    field.scroll_to_caret(&atlas);
+    let caret = atlas.caret(&field.text, field.caret, field.layout_options());
+    assert!(caret.x + caret.width - field.scroll <= field.width);
+    field.click(&atlas, Vec2::new(0.0, 0.0), false);
+    field.release();
+    assert_eq!(field.caret, 15);
+
+    field.set_caret(0, false);
+    field.scroll_to_caret(&atlas);
+    assert_eq!(field.scroll, 0.0);
+
+    //Deleting doesn't leave the text scrolled past its end.
+    let mut field = TextField::new()
+        .with_width(advance * 5.0)
+        .with_text(&"a".repeat(20));
+    field.scroll_to_caret(&atlas);
+    field.select_all();
+    field.backspace(false);
+    field.scroll_to_caret(&atlas);
+    assert_eq!(field.scroll, 0.0);
+}
+
+#[test]
+pub fn clip_quads() {
+    let corner = |x: f32, u: f32| {
+        Vertex::default()
+            .position(Vec2::new(x, 0.0))
+            .uv(Vec2::new(u, 0.0))
+    };
+    let mut quad = [
+        corner(0.0, 0.0),
+        corner(0.0, 0.0),
+        corner(10.0, 1.0),
+        corner(10.0, 1.0),
+        corner(10.0, 1.0),
+        corner(0.0, 0.0),
+    ];
+    clip_x(&mut quad, 5.0, 20.0);
+    for vertex in &quad {
+        assert!(
+            vertex.position.x == 5.0 && vertex.uv.x == 0.5
+                || vertex.position.x == 10.0 && vertex.uv.x == 1.0
+        );
+    }
+
+    //Entirely outside, nothing is left to draw.
+    clip_x(&mut quad, 20.0, 30.0);
+    assert!(quad.iter().all(|vertex| vertex.position.x == 20.0));
+}
+
+#[test]
+pub fn bitmap_pitch() {
+    //Two rows of two pixels padded to four bytes, the top row is 1, 2.
+    let down = [1, 2, 0, 0, 3, 4, 0, 0];
+    let up = [3, 4, 0, 0, 1, 2, 0, 0];
+    assert_eq!(bitmap_rows(&down, 4), [&down[..4], &down[4..]]);
+    assert_eq!(bitmap_rows(&up, -4), [&down[..4], &down[4..]]);
+    assert!(bitmap_rows(&[], 0).is_empty());
+}
+
+#[test]
+pub fn color_pixels() {
+    //Half transparent blue, premultiplied BGRA.
+    let mut pixels = [128, 0, 0, 128, 0, 0, 0, 0];
+    bgra_to_rgba(&mut pixels);
+    assert_eq!(pixels, [0, 0, 255, 128, 0, 0, 0, 0]);
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn color_page() {
+    let mut atlas = cascadia(16);
+    assert!(atlas.color.is_none());
+
+    let key = GlyphKey {
+        face: 0,
+        id: 9999,
+        subpixel: 0,
+    };
+    let emoji = Glyph {
+        width: 2.0,
+        height: 2.0,
+        color: true,
+        ..Default::default()
+    };
+    let mut page = AtlasBitmap::new(Mode::Color);
+    page.insert(key, emoji, &[255; 16]).unwrap();
+    atlas.color = Some(page);
+
+    assert!(atlas.glyph(key).unwrap().color);
+    assert!(!atlas.glyph(SOLID).unwrap().color);
+
+    //Color glyphs aren't baked.
+    let font = include_bytes!("../CascadiaMono.ttf");
+    let baked =
+        Atlas::from_baked(&atlas.bake_png().unwrap(), &atlas.bake_metrics(), &[font]).unwrap();
+    assert!(baked.color.is_none());
+}
+
+fn area(triangles: &[Vec2]) -> f32 {
+    triangles
+        .chunks_exact(3)
+        .map(|t| {
+            ((t[1].x - t[0].x) * (t[2].y - t[0].y) - (t[2].x - t[0].x) * (t[1].y - t[0].y)) / 2.0
+        })
+        .sum()
+}
+
+#[test]
+pub fn tessellate_nonzero() {
+    let square = |x: f32, y: f32, size: f32| {
+        vec![
+            Vec2::new(x, y),
+            Vec2::new(x + size, y),
+            Vec2::new(x + size, y + size),
+            Vec2::new(x, y + size),
+        ]
+    };
+
+    //Every triangle is counter clockwise, so the signed area is the filled area.
+    let triangles = tessellate(&[square(0.0, 0.0, 10.0)]);
+    assert_eq!(triangles.len() % 3, 0);
+    assert_eq!(area(&triangles), 100.0);
+
+    //A hole winds the other way.
+    let mut hole = square(2.0, 2.0, 4.0);
+    hole.reverse();
+    assert_eq!(area(&tessellate(&[square(0.0, 0.0, 10.0), hole])), 84.0);
+
+    //Overlapping contours that wind the same way are filled once.
+    let overlap = tessellate(&[square(0.0, 0.0, 10.0), square(5.0, 5.0, 10.0)]);
+    assert_eq!(area(&overlap), 175.0);
+
+    //Self intersecting bow tie, both halves are filled.
+    let bow = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(10.0, 10.0),
+        Vec2::new(10.0, 0.0),
+        Vec2::new(0.0, 10.0),
+    ];
+    assert!((area(&tessellate(&[bow.to_vec()])) - 50.0).abs() < 1e-3);
+
+    //Curves are flattened to within the tolerance.
+    let circle = GlyphOutline {
+        segments: vec![
+            PathSegment::MoveTo(Vec2::new(1.0, 0.0)),
+            PathSegment::ConicTo(Vec2::new(1.0, 1.0), Vec2::new(0.0, 1.0)),
+            PathSegment::ConicTo(Vec2::new(-1.0, 1.0), Vec2::new(-1.0, 0.0)),
+            PathSegment::ConicTo(Vec2::new(-1.0, -1.0), Vec2::new(0.0, -1.0)),
+            PathSegment::ConicTo(Vec2::new(1.0, -1.0), Vec2::new(1.0, 0.0)),
+        ],
+    };
+    let coarse = circle.flatten(100.0, 10.0)[0].len();
+    let fine = circle.flatten(100.0, 0.1)[0].len();
+    assert!(fine > coarse * 4);
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn glyph_outline() {
+    let atlas = cascadia(32);
+    let id = atlas.faces[0].shaper.face.glyph_index('o').unwrap().0 as u32;
+    let outline = atlas
+        .outline(GlyphKey {
+            face: 0,
+            id,
+            subpixel: 0,
+        })
+        .unwrap();
+    let moves = outline
+        .segments
+        .iter()
+        .filter(|s| matches!(s, PathSegment::MoveTo(_)))
+        .count();
+    assert_eq!(moves, 2);
+
+    //The filled area is close to the ink FreeType rasterizes at the same size.
+    let ink: f32 = {
+        let mut atlas = cascadia(200);
+        let glyph = atlas
+            .glyph(GlyphKey {
+                face: 0,
+                id,
+                subpixel: 0,
+            })
+            .unwrap();
+        let (x, y) = (glyph.x as usize, glyph.y as usize);
+        let row = atlas.bitmap.width as usize;
+        (0..glyph.height as usize)
+            .flat_map(|j| (0..glyph.width as usize).map(move |i| (i, j)))
+            .map(|(i, j)| atlas.bitmap.pixels[(y + j) * row + x + i] as f32 / 255.0)
+            .sum()
+    };
+    let triangles = atlas.tessellate_text("o", 200.0, TextOptions::default());
+    let filled = area(&triangles);
+    assert!((filled - ink).abs() / ink < 0.02, "{filled} vs {ink}");
+
+    //Twice the size is four times the area.
+    let bigger = area(&atlas.tessellate_text("o", 400.0, TextOptions::default()));
+    assert!((bigger / filled - 4.0).abs() < 0.02);
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn subpixel() {
+    let mut atlas = cascadia(13);
+
+    //Advances keep their fraction, so a long line doesn't drift.
+    let face = &atlas.faces[0].shaper;
+    let id = face.face.glyph_index('a').unwrap();
+    let advance = face.face.glyph_hor_advance(id).unwrap() as f32 * face.scale;
+    assert_ne!(advance, advance.round());
+    let width = atlas.measure(&"a".repeat(100)).width;
+    assert!((width - advance * 100.0).abs() < 0.01);
+
+    assert_eq!(atlas.snap(10.3), (10.0, 0));
+    atlas.set_subpixel(4);
+    assert_eq!(atlas.snap(10.3), (10.0, 1));
+    assert_eq!(atlas.snap(10.9), (11.0, 0));
+    assert_eq!(atlas.snap(-0.2), (-1.0, 3));
+
+    //Each offset is its own bitmap.
+    let pixels = |atlas: &mut Atlas, subpixel| {
+        let glyph = atlas
+            .glyph(GlyphKey {
+                face: 0,
+                id: id.0 as u32,
+                subpixel,
+            })
+            .unwrap();
+        let row = atlas.bitmap.width as usize;
+        (0..glyph.height as usize)
+            .flat_map(|y| {
+                let start = (glyph.y as usize + y) * row + glyph.x as usize;
+                atlas.bitmap.pixels[start..start + glyph.width as usize].to_vec()
+            })
+            .collect::<Vec<u8>>()
+    };
+    assert_ne!(pixels(&mut atlas, 0), pixels(&mut atlas, 2));
+
+    atlas.preload("ab");
+    let font = include_bytes!("../CascadiaMono.ttf");
+    let baked =
+        Atlas::from_baked(&atlas.bake_png().unwrap(), &atlas.bake_metrics(), &[font]).unwrap();
+    assert_eq!(baked.subpixel, 4);
+    assert_eq!(baked.bitmap.glyphs.len(), atlas.bitmap.glyphs.len());
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn font_options() {
+    let mut atlas = cascadia(16);
+    let gray = ink(&mut atlas, 'e');
+
+    atlas.set_options(FontOptions {
+        antialias: Antialias::Mono,
+        ..Default::default()
+    });
+    assert!(atlas.bitmap.pixels.iter().all(|&p| p == 0 || p == 255));
+    assert!(ink(&mut atlas, 'e') > 0);
+
+    let advance = atlas.measure("e").width;
+    atlas.set_options(FontOptions {
+        embolden: 1.0,
+        ..Default::default()
+    });
+    assert!(ink(&mut atlas, 'e') > gray);
+    //The advance grows with the outline so neighbours don't overlap.
+    assert_eq!(atlas.measure("e").width, advance + 1.0);
+    let font = include_bytes!("../CascadiaMono.ttf");
+    let baked =
+        Atlas::from_baked(&atlas.bake_png().unwrap(), &atlas.bake_metrics(), &[font]).unwrap();
+    assert_eq!(baked.measure("e").width, advance + 1.0);
+
+    //Each hinting mode moves the outline differently.
+    let hinted: Vec<u32> = [Hinting::None, Hinting::Light, Hinting::Normal]
+        .into_iter()
+        .map(|hinting| {
+            atlas.set_options(FontOptions {
+                hinting,
+                ..Default::default()
+            });
+            ink(&mut atlas, 'e')
+        })
+        .collect();
+    assert_eq!(hinted[2], gray);
+    assert_ne!(hinted[0], hinted[2]);
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn missing_question_mark() {
+    let lib = freetype::Library::init().unwrap();
+    //JetBrains Mono with '?' taken out of its cmap.
+    let font = include_bytes!("../JetBrainsMono-NoQuestionMark.ttf");
+    let mut atlas = Atlas::new(&lib, font, 16, Mode::Coverage);
+    assert_eq!(atlas.fallback.id, 0);
+
+    //Characters the font doesn't have draw the `.notdef` box.
+    let glyph = atlas.layout("?", TextOptions::default())[0].glyphs[0];
+    assert_eq!(glyph.id, 0);
+    let notdef = atlas.positioned_glyph(&glyph, 0);
+    assert!(notdef.width > 0.0);
+
+    //Baked atlases can't rasterize anything, `.notdef` has to be in the file.
+    let mut baked =
+        Atlas::from_baked(&atlas.bake_png().unwrap(), &atlas.bake_metrics(), &[font]).unwrap();
+    let glyphs = &baked.layout("?é", TextOptions::default())[0].glyphs;
+    for glyph in glyphs {
+        let drawn = baked.positioned_glyph(glyph, 0);
+        assert_eq!((drawn.x, drawn.y), (notdef.x, notdef.y));
+    }
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn sdf_margin() {
+    let lib = freetype::Library::init().unwrap();
+    let font = include_bytes!("../CascadiaMono.ttf");
+    let mut sdf = Atlas::new(&lib, font, 32, Mode::Sdf);
+    let mut coverage = cascadia(32);
+    let id = sdf.faces[0].shaper.face.glyph_index('o').unwrap().0 as u32;
+    let key = GlyphKey {
+        face: 0,
+        id,
+        subpixel: 0,
+    };
+
+    let glyph = sdf.glyph(key).unwrap();
+    let plain = coverage.glyph(key).unwrap();
+    let margin = SDF_MARGIN as f32;
+    assert!(glyph.width > plain.width + margin * 2.0);
+    assert!(glyph.bearing.x < plain.bearing.x - margin);
+    assert!(glyph.bearing.y > plain.bearing.y + margin);
+
+    //The margin is as far outside as the field goes, the middle of the stroke is inside.
+    let row = sdf.bitmap.width as usize;
+    let texel =
+        |x: usize, y: usize| sdf.bitmap.pixels[(glyph.y as usize + y) * row + glyph.x as usize + x];
+    let (w, h) = (glyph.width as usize, glyph.height as usize);
+    for i in 0..SDF_MARGIN as usize {
+        assert!((0..w).all(|x| texel(x, i) == 0 && texel(x, h - 1 - i) == 0));
+        assert!((0..h).all(|y| texel(i, y) == 0 && texel(w - 1 - i, y) == 0));
+    }
+    assert!((0..w).any(|x| texel(x, h / 2) > 128));
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn lcd_channels() {
+    let lib = freetype::Library::init().unwrap();
+    let font = include_bytes!("../CascadiaMono.ttf");
+    let lcd = |order| {
+        let filter = LcdFilter::Default;
+        let mut atlas = Atlas::new(&lib, font, 24, Mode::Lcd { order, filter });
+        let id = atlas.faces[0].shaper.face.glyph_index('o').unwrap().0 as u32;
+        let glyph = atlas
+            .glyph(GlyphKey {
+                face: 0,
+                id,
+                subpixel: 0,
+            })
+            .unwrap();
+
+        //Three bytes per pixel, the width is in pixels rather than subpixels.
+        assert_eq!(atlas.bitmap.mode.channels(), 3);
+        let row = atlas.bitmap.width as usize * 3;
+        let (x, w) = (glyph.x as usize * 3, glyph.width as usize * 3);
+        (0..glyph.height as usize)
+            .flat_map(|y| {
+                let start = (glyph.y as usize + y) * row + x;
+                atlas.bitmap.pixels[start..start + w].to_vec()
+            })
+            .collect::<Vec<u8>>()
+    };
+
+    let rgb = lcd(PixelOrder::Rgb);
+    let bgr = lcd(PixelOrder::Bgr);
+    assert_eq!(rgb.len(), bgr.len());
+    //Curved edges cover the subpixels unevenly.
+    assert!(rgb.chunks_exact(3).any(|p| p[0] != p[2]));
+    for (rgb, bgr) in rgb.chunks_exact(3).zip(bgr.chunks_exact(3)) {
+        assert_eq!([rgb[2], rgb[1], rgb[0]], bgr);
+    }
+}
+
+#[test]
+#[cfg(feature = "freetype")]
+pub fn fallback_faces() {
+    let lib = freetype::Library::init().unwrap();
+    let mut atlas = Atlas::new(
+        &lib,
+        include_bytes!("../JetBrainsMono.ttf"),
+        16,
+        Mode::Coverage,
+    );
+    atlas.add_fallback(&lib, include_bytes!("../CascadiaMono.ttf"));
+
+    let glyphs = &atlas.layout("A\u{132}\u{10FFFD}", TextOptions::default())[0].glyphs;
+    assert_eq!(glyphs[0].face, 0);
+    //Missing from the primary face.
+    assert_eq!(glyphs[1].face, 1);
+    assert_ne!(glyphs[1].id, 0);
+    //Missing from every face, drawn with the primary face's '?'.
+    assert_eq!(glyphs[2].id, 0);
+    let missing = atlas.positioned_glyph(&glyphs[2], 0);
+    let question = atlas.glyph(atlas.fallback).unwrap();
+    assert_eq!((missing.x, missing.y), (question.x, question.y));
+}
+
+#[cfg(feature = "freetype")]
+#[test]
+pub fn rich_tabs() {
+    let atlases = [cascadia(16), cascadia(32)];
+    let spans = [
+        Span::new("a", Vec4::default()),
+        Span::new("b\tc", Vec4::default()).with_atlas(1),
+    ];
+    let rich = layout_rich(&atlases, &spans, TextOptions::default());
+
+    //The tab is in the second span, so it stops on that span's grid.
+    let tab_width = atlases[1].tab_width(TextOptions::default());
+    let glyphs = &rich.lines[0].glyphs;
+    assert_eq!(glyphs[3].position.x, tab_width);
+}
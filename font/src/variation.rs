@@ -0,0 +1,152 @@
+use crate::*;
+#[cfg(feature = "freetype")]
+use freetype::Face;
+use rustybuzz::ttf_parser::{Tag, VariationAxis};
+
+/// A position on one of a variable font's design axes. Axes the font doesn't have are ignored
+/// and values are clamped to the axis range.
+///
+/// ```rs
+/// let mut atlas = load_font_variations(&rd, font, 32, &[Variation::weight(700.0)]);
+/// atlas.set_variations(&[Variation::weight(350.0)]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variation {
+    pub tag: [u8; 4],
+    pub value: f32,
+}
+
+impl Variation {
+    pub const fn new(tag: &[u8; 4], value: f32) -> Self {
+        Self { tag: *tag, value }
+    }
+
+    /// `wght`, 400 is regular and 700 is bold.
+    pub const fn weight(value: f32) -> Self {
+        Self::new(b"wght", value)
+    }
+
+    /// `wdth`, percent of the normal width.
+    pub const fn width(percent: f32) -> Self {
+        Self::new(b"wdth", percent)
+    }
+
+    /// `slnt`, in degrees. Negative values lean to the right.
+    pub const fn slant(degrees: f32) -> Self {
+        Self::new(b"slnt", degrees)
+    }
+
+    /// `ital`, switches to the italic glyphs.
+    pub const fn italic(italic: bool) -> Self {
+        Self::new(b"ital", if italic { 1.0 } else { 0.0 })
+    }
+}
+
+/// Value for each of the font's axes in `fvar` order, the default where `variations` doesn't
+/// mention it.
+fn coordinates(axes: &[VariationAxis], variations: &[Variation]) -> Vec<f32> {
+    axes.iter()
+        .map(|axis| {
+            variations
+                .iter()
+                .rev()
+                .find(|variation| Tag::from_bytes(&variation.tag) == axis.tag)
+                .map_or(axis.def_value, |variation| {
+                    variation.value.clamp(axis.min_value, axis.max_value)
+                })
+        })
+        .collect()
+}
+
+impl Shaper {
+    /// Axes of the font, empty unless it's a variable font.
+    pub fn axes(&self) -> Vec<VariationAxis> {
+        self.face.variation_axes().into_iter().collect()
+    }
+
+    /// Move every axis to its value in `variations` or back to its default.
+    pub fn set_variations(&mut self, variations: &[Variation]) {
+        let axes = self.axes();
+        for (axis, value) in axes.iter().zip(coordinates(&axes, variations)) {
+            self.face.set_variation(axis.tag, value);
+        }
+    }
+}
+
+impl FontFace {
+    /// Apply `variations` to both the shaper and the rasterizer so advances match the outlines.
+    pub fn set_variations(&mut self, variations: &[Variation]) {
+        self.shaper.set_variations(variations);
+
+        #[cfg(feature = "freetype")]
+        if let Some(face) = &mut self.face {
+            set_design_coordinates(face, &self.shaper.axes(), variations);
+        }
+    }
+}
+
+/// FreeType wants every axis at once, as 16.16 fixed point.
+///https://freetype.org/freetype2/docs/reference/ft2-multiple_masters.html#ft_set_var_design_coordinates
+#[cfg(feature = "freetype")]
+fn set_design_coordinates(
+    face: &mut Face<&'static [u8]>,
+    axes: &[VariationAxis],
+    variations: &[Variation],
+) {
+    if axes.is_empty() {
+        return;
+    }
+
+    let coords: Vec<freetype::ffi::FT_Fixed> = coordinates(axes, variations)
+        .into_iter()
+        .map(|value| (value * 65536.0).round() as freetype::ffi::FT_Fixed)
+        .collect();
+
+    unsafe {
+        let error = freetype::ffi::FT_Set_Var_Design_Coordinates(
+            face.raw_mut(),
+            coords.len() as u32,
+            coords.as_ptr(),
+        );
+        assert_eq!(error, 0, "FT_Set_Var_Design_Coordinates failed");
+    }
+}
+
+impl Atlas {
+    /// Design axes of the primary face, see [`Atlas::set_variations`].
+    pub fn axes(&self) -> Vec<VariationAxis> {
+        self.faces[0].shaper.axes()
+    }
+}
+
+#[cfg(feature = "freetype")]
+impl Atlas {
+    /// Move every face to a new position in its design space. Existing glyphs are thrown away
+    /// and rasterized again as they're drawn, so animating an axis costs a rasterization per
+    /// glyph per frame.
+    pub fn set_variations(&mut self, variations: &[Variation]) {
+        self.variations = variations.to_vec();
+        for face in &mut self.faces {
+            face.set_variations(variations);
+        }
+        self.set_metrics();
+        self.reload();
+    }
+}
+
+/// Rasterize `font` at `size` pixels per em with its design axes at `variations`.
+///
+/// # Safety
+/// Uploads the atlas, the GL context of `rd` has to be current on this thread.
+#[cfg(feature = "freetype")]
+pub unsafe fn load_font_variations(
+    rd: &Renderer,
+    font: &'static [u8],
+    size: u32,
+    variations: &[Variation],
+) -> Atlas {
+    let lib = freetype::Library::init().unwrap();
+    let mut atlas = Atlas::new_with_variations(&lib, font, size, Mode::Coverage, variations);
+    atlas.upload(rd.gl);
+    atlas
+}